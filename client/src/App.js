@@ -1,4 +1,4 @@
-import React from 'react';
+import React, { useState, useEffect } from 'react';
 import './fonts.css';
 import './App.css';
 
@@ -7,17 +7,41 @@ import { useSelector } from 'react-redux';
 import Landing from './landing/Landing';
 import Room from './room/Room';
 
+function RestartBar({ timestamp }) {
+    const [seconds, setSeconds] = useState(null);
+
+    useEffect(() => {
+        const interval = setInterval(() => {
+            setSeconds(Math.max(0, Math.ceil((timestamp - new Date()) / 1000)));
+        }, 100);
+        return () => {
+            clearInterval(interval);
+        }
+    }, [timestamp, setSeconds]);
+
+    if (seconds === null) {
+        return null;
+    }
+
+    return (
+        <div className="connection-bar restart-bar">
+            The server is restarting in {seconds} second{seconds === 1? '': 's'}, no new rooms can be made until it's back
+        </div>
+    );
+}
+
 function App({ socketManager }) {
     const inRoom = !!useSelector(state => state.room);
     const disconnected = useSelector(
         state => state.socketState === 'disconnected'
     );
+    const restartingAt = useSelector(state => state.restartingAt);
 
     const connectionBar = disconnected ? (
         <div className="connection-bar">
             There seems to be a connection issue...
         </div>
-    ) : null;
+    ) : restartingAt ? <RestartBar timestamp={restartingAt} /> : null;
 
     const view = inRoom ? <Room socketManager={socketManager} /> : null;
 
@@ -14,7 +14,8 @@ function usePrevious(value) {
 }
 
 function EnterRoom({ username, socketManager, enabled }) {
-    const [roomCode, roomCodeField] = useInput({ placeholder: 'Room code', maxlength: 5 });
+    const [roomCode, roomCodeField] = useInput({ placeholder: 'Room code', maxlength: 16 });
+    const [vanityCode, vanityCodeField] = useInput({ placeholder: 'Custom code (optional)', maxlength: 16 });
     const disabled = useSelector(state => state.socketState !== 'connected');
 
     const prevCode = usePrevious(roomCode);
@@ -37,6 +38,24 @@ function EnterRoom({ username, socketManager, enabled }) {
         };
     }, [socketManager, setError, roomCode, username]);
 
+    useEffect(() => {
+        socketManager.setCreateRoomErrorHandler((problem, code) => {
+            if (code !== vanityCode) {
+                setError(null);
+            } else if (problem === 'taken') {
+                setError(<>The room code <code>{code}</code> is already in use.</>);
+            } else if (problem === 'filtered') {
+                setError(<>The room code <code>{code}</code> isn't allowed.</>);
+            } else {
+                setError('Custom room codes must be 3 to 16 letters and numbers.');
+            }
+        });
+
+        return () => {
+            socketManager.setCreateRoomErrorHandler(null);
+        };
+    }, [socketManager, setError, vanityCode]);
+
     const joinRoomSubmit = e => {
         e.preventDefault();
 
@@ -51,8 +70,8 @@ function EnterRoom({ username, socketManager, enabled }) {
 
     const createRoomSubmit = e => {
         e.preventDefault();
-        console.debug('Creating room with username', username);
-        socketManager.createRoom(username);
+        console.debug('Creating room with username', username, 'and code', vanityCode);
+        socketManager.createRoom(username, vanityCode);
     };
 
     useEffect(() => {
@@ -70,6 +89,7 @@ function EnterRoom({ username, socketManager, enabled }) {
             </form>
             <hr />
             <form className="create-room" onSubmit={createRoomSubmit}>
+                {vanityCodeField}
                 <input type="submit" value="Create Room" disabled={disabled} />
             </form>
             <p className="error">{error}</p>
@@ -1,6 +1,9 @@
-import React, { useEffect, useState, useCallback } from 'react';
+import React, { useEffect, useState, useCallback, useRef } from 'react';
 import { useSelector } from 'react-redux';
 
+import { PALETTE, renderCommand as renderCommandTo } from './drawing';
+import { strokeKey } from '../state/socket';
+
 function useCanvasContext() {
     const [context, setContext] = useState(null);
     const [canvas, setCanvas] = useState(null);
@@ -16,13 +19,14 @@ function useCanvasContext() {
     return [context, canvas, canvasRef];
 }
 
-function Pen({ penSize, x, y }) {
+function Pen({ penSize, x, y, colour }) {
 
     const style = {
         width: penSize * 2,
         height: penSize * 2,
         top: y - penSize,
-        left: x - penSize
+        left: x - penSize,
+        backgroundColor: colour
     };
 
     return (
@@ -30,14 +34,14 @@ function Pen({ penSize, x, y }) {
     );
 }
 
-function PenChanger({ penSize, setPenSize }) {
+function PenChanger({ penSize, setPenSize, maxPenSize }) {
 
     return (
         <div className="pen-changer">
             <input
                 type="range"
                 min="1"
-                max="10"
+                max={maxPenSize}
                 value={penSize}
                 onChange={e => setPenSize(e.target.value)}
             />
@@ -45,16 +49,51 @@ function PenChanger({ penSize, setPenSize }) {
     );
 }
 
+function ToolPicker({ tool, setTool, colour, setColour }) {
+
+    return (
+        <div className="tool-picker">
+            {PALETTE.map((hex, index) => (
+                <div
+                    key={hex}
+                    className={tool !== 'eraser' && colour === index? 'swatch selected': 'swatch'}
+                    style={{ backgroundColor: hex }}
+                    onClick={() => setColour(index)} >
+                </div>
+            ))}
+            {['pen', 'eraser', 'fill'].map(name => (
+                <input
+                    key={name}
+                    type="button"
+                    className={tool === name? 'tool-button selected': 'tool-button'}
+                    onClick={() => setTool(name)}
+                    value={name} />
+            ))}
+        </div>
+    );
+}
+
+const DEFAULT_CANVAS = { width: 500, height: 500, maxPenSize: 10 };
+
 export default function Canvas({ socketManager, isLeader }) {
     const [context, canvas, canvasRef] = useCanvasContext();
     const [penDown, setPenDown] = useState(false);
     const [penLeft, setPenLeft] = useState(false);
 
     const canvasClearing = useSelector(state => state.room.canvasClearing);
+    const canvasSettings = useSelector(state => state.room.canvas) || DEFAULT_CANVAS;
+    const socketID = useSelector(state => state.socketID);
 
     const [prevX, setPrevX] = useState(0);
     const [prevY, setPrevY] = useState(0);
     const [penSize, setPenSize] = useState(2);
+    const [tool, setTool] = useState('pen');
+    const [colour, setColour] = useState(0);
+
+    // Keep the pen within the room's limit when the settings change
+    useEffect(() => {
+        setPenSize(size => Math.min(size, canvasSettings.maxPenSize));
+    }, [canvasSettings.maxPenSize]);
 
     useEffect(() => {
         if (canvas) {
@@ -62,56 +101,110 @@ export default function Canvas({ socketManager, isLeader }) {
         }
     }, [canvas]);
 
-    const drawLine = useCallback((startX, startY, endX, endY, penSize) => {
+    // Strokes currently on the canvas, kept so that the canvas can be redrawn when
+    // one of them is undone
+    const strokes = useRef([]);
+    const strokeID = useRef(0);
+
+    const renderCommand = useCallback(command => {
         if (!context) {
             console.error('Context wasn\'t available during line drawing');
             return;
         }
 
-        context.beginPath();
-        context.ellipse(startX, startY, penSize, penSize, 0, 0, 2 * Math.PI);
-        context.fill();
+        renderCommandTo(context, command);
+    }, [context]);
 
-        context.beginPath();
-        context.lineWidth = penSize * 2;
-        context.moveTo(endX, endY);
-        context.lineTo(startX, startY);
-        context.stroke();
-        context.closePath();
+    const recordCommand = useCallback(command => {
+        const id = command[1];
+        const lastStroke = strokes.current[strokes.current.length - 1];
+        if (lastStroke && lastStroke.id === id) {
+            lastStroke.commands.push(command);
+        } else {
+            strokes.current.push({ id, commands: [command] });
+        }
+    }, []);
+
+    const drawCommand = useCallback(command => {
+        renderCommand(command);
 
         if (isLeader) {
-            socketManager.sendDraw([startX, startY, endX, endY, penSize]);
+            const [type, id, ...params] = command;
+            recordCommand([type, strokeKey(socketID, id), ...params]);
+            socketManager.sendDrawCommand(command);
         }
-    }, [context, socketManager, isLeader]);
+    }, [renderCommand, recordCommand, socketManager, isLeader, socketID]);
 
     const drawCleanLine = useCallback((startX, startY, endX, endY, penSize) => {
-        drawLine.apply(null, [startX, startY, endX, endY, penSize]
+        const { width, height } = canvasSettings;
+        const params = [startX, startY, endX, endY, penSize]
             .map(x => Math.round(x))
             .map(x => x < 0? 0: x)
-            .map(x => x > 500? 500: x)
-        );
-    }, [drawLine]);
+            .map((x, i) => Math.min(x, i === 4? canvasSettings.maxPenSize: i % 2 === 0? width: height));
+        if (tool === 'eraser') {
+            drawCommand(['erase', strokeID.current, ...params]);
+        } else {
+            drawCommand(['draw', strokeID.current, ...params, colour]);
+        }
+    }, [drawCommand, tool, colour, canvasSettings]);
 
-    const clearCanvas = useCallback(() => {
+    const wipeCanvas = useCallback(() => {
         if (context)
             context.clearRect(0, 0, canvas.width, canvas.height);
     }, [context, canvas]);
 
+    const clearCanvas = useCallback(() => {
+        strokes.current = [];
+        wipeCanvas();
+    }, [wipeCanvas]);
+
+    const redrawCanvas = useCallback(() => {
+        wipeCanvas();
+        strokes.current.forEach(stroke => {
+            stroke.commands.forEach(renderCommand);
+        });
+    }, [wipeCanvas, renderCommand]);
+
     const eraseCanvas = useCallback(() => {
         socketManager.clear();
         clearCanvas();
     }, [socketManager, clearCanvas]);
 
+    const undo = useCallback(() => {
+        socketManager.undo();
+    }, [socketManager]);
+
+    const redo = useCallback(() => {
+        socketManager.redo();
+    }, [socketManager]);
+
     useEffect(() => {
         // Only set the handler when the context is valid
         if (context) {
-            socketManager.setDrawHandler((erase, startX, startY, endX, endY, penSize) => {
-                if (!isLeader) {
-                    if (erase) {
-                        clearCanvas();
-                    } else {
-                        drawCleanLine(startX, startY, endX, endY, penSize);
-                    }
+            socketManager.setDrawHandler((type, ...params) => {
+                if (type === 'clear') {
+                    clearCanvas();
+                } else if (type === 'snapshot') {
+                    const [commands] = params;
+                    clearCanvas();
+                    commands.forEach(command => {
+                        renderCommand(command);
+                        recordCommand(command);
+                    });
+                } else if (type === 'undo') {
+                    const [id] = params;
+                    strokes.current = strokes.current.filter(stroke => stroke.id !== id);
+                    redrawCanvas();
+                } else if (type === 'redo') {
+                    const [id, commands] = params;
+                    strokes.current.push({ id, commands });
+                    commands.forEach(renderCommand);
+                } else if (!params[0].startsWith(socketID + ':')) {
+                    // Everyone has already drawn their own lines, but there can be
+                    // others drawing on the same canvas
+                    const command = [type, ...params];
+                    renderCommand(command);
+                    recordCommand(command);
                 }
             });
 
@@ -119,7 +212,7 @@ export default function Canvas({ socketManager, isLeader }) {
                 socketManager.setDrawHandler(null);
             }
         }
-    }, [drawCleanLine, socketManager, isLeader, clearCanvas, context]);
+    }, [renderCommand, recordCommand, redrawCanvas, socketManager, socketID, clearCanvas, context]);
 
     // Consider whether this is the correct control flow, feels a bit hacky
     useEffect(() => {
@@ -156,18 +249,25 @@ export default function Canvas({ socketManager, isLeader }) {
             const rect = canvas.getBoundingClientRect();
             const x = e.clientX - rect.left;
             const y = e.clientY - rect.top;
-            drawCleanLine(x, y, x, y, penSize);
+            strokeID.current += 1;
+            if (tool === 'fill') {
+                setPenDown(false);
+                drawCommand(['fill', strokeID.current, Math.round(x), Math.round(y), colour]);
+            } else {
+                drawCleanLine(x, y, x, y, penSize);
+            }
 
             setPrevX(x);
             setPrevY(y);
         }
-    }, [isLeader, canvas, drawCleanLine, penSize]);
+    }, [isLeader, canvas, drawCleanLine, drawCommand, penSize, tool, colour]);
 
     const mouseEnter = useCallback(e => {
         if (isLeader && penDown && canvas) {
             const rect = canvas.getBoundingClientRect();
             const x = e.clientX - rect.left;
             const y = e.clientY - rect.top;
+            strokeID.current += 1;
             drawCleanLine(x, y, x, y, penSize);
 
             setPrevX(x);
@@ -203,19 +303,22 @@ export default function Canvas({ socketManager, isLeader }) {
         <>
             <div className={isLeader? "draw-toolbar": "draw-toolbar hide"}>
                 {canvasClearing? <input className="clear-button" type="submit" onClick={eraseCanvas} value="Clear canvas"  />: null}
+                <input className="undo-button" type="submit" onClick={undo} value="Undo" />
+                <input className="undo-button" type="submit" onClick={redo} value="Redo" />
                 <p className="pen-changer-label" >pen size: </p>
-                <PenChanger penSize={penSize} setPenSize={setPenSize} />
+                <PenChanger penSize={penSize} setPenSize={setPenSize} maxPenSize={canvasSettings.maxPenSize} />
+                <ToolPicker {...{ tool, setTool, colour, setColour }} />
             </div>
             <div className={isLeader? "canvas-wrapper hide-cursor": "canvas-wrapper"}>
-                {isLeader? <Pen {...{ penSize, x: prevX, y: prevY }} />: null}
+                {isLeader? <Pen {...{ penSize, x: prevX, y: prevY, colour: tool === 'eraser'? PALETTE[1]: PALETTE[colour] }} />: null}
                 <canvas
                     ref={canvasRef}
                     onMouseDown={mouseDown}
                     onMouseMove={mouseMove}
                     onMouseEnter={mouseEnter}
                     onMouseLeave={mouseLeft}
-                    width="500"
-                    height="500">
+                    width={canvasSettings.width}
+                    height={canvasSettings.height}>
                 </canvas>
             </div>
             <input className="download-button" type="submit" value="Download drawing" onClick={download} />
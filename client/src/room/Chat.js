@@ -27,7 +27,7 @@ function Message({ message, users }) {
                     <span className="username">{message.winner.username} </span>
                     correctly guessed the word
                     <span className="word"> {message.word}{message.alternate? ` (${message.alternate})`: ''}</span>
-                    , they now have {message.points} point{message.points>1? 's': ''}
+                    {message.team >= 0? `, team ${message.team + 1} now has`: ', they now have'} {message.points} point{message.points>1? 's': ''}
                 </div>
             );
         case 'user_join':
@@ -44,6 +44,13 @@ function Message({ message, users }) {
                     left the room
                 </div>
             );
+        case 'blocked':
+            return (
+                <div className="message blocked">
+                    Your message wasn't sent since it gives away the word:
+                    <span className="content"> {message.content}</span>
+                </div>
+            );
         case 'timeout':
             return (
                 <div className="message timeout">
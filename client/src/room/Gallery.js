@@ -0,0 +1,71 @@
+import React, { useCallback, useEffect, useRef, useState } from 'react';
+import { useSelector } from 'react-redux';
+
+import { renderCommand } from './drawing';
+
+function Drawing({ round, index, users, roomCode }) {
+    const canvasRef = useRef(null);
+    const [replaying, setReplaying] = useState(false);
+
+    const renderAll = useCallback(() => {
+        const context = canvasRef.current.getContext('2d');
+        context.clearRect(0, 0, round.width, round.height);
+        round.commands.forEach(command => renderCommand(context, command));
+    }, [round]);
+
+    useEffect(renderAll, [renderAll]);
+
+    // Draws the commands again at the same speed they were originally drawn
+    useEffect(() => {
+        if (replaying) {
+            const context = canvasRef.current.getContext('2d');
+            context.clearRect(0, 0, round.width, round.height);
+            const start = round.times.length > 0? Math.min(...round.times): 0;
+            const timeouts = round.commands.map((command, i) => setTimeout(() => {
+                renderCommand(context, command);
+            }, round.times[i] - start));
+            timeouts.push(setTimeout(() => setReplaying(false), Math.max(0, ...round.times) - start));
+            return () => {
+                timeouts.forEach(clearTimeout);
+                renderAll();
+            }
+        }
+    }, [replaying, round, renderAll]);
+
+    // Everyone who drew in the round, which can be more than the leader
+    const authors = [...new Set([round.leaderID, ...round.commands.map(command => command[1].split(':')[0])])];
+    const drawers = authors.map(id => users[id]? users[id].username: 'someone who left');
+    const winner = round.winnerID? users[round.winnerID]: null;
+
+    return (
+        <div className="gallery-drawing">
+            <canvas ref={canvasRef} width={round.width} height={round.height}></canvas>
+            <div className="gallery-word">{round.word}</div>
+            <div className="gallery-caption">
+                Drawn by {drawers.join(' and ')}
+                {winner? `, guessed by ${winner.username}`: ', nobody guessed it'}
+            </div>
+            <input type="button" value={replaying? 'Stop': 'Replay'} onClick={() => setReplaying(!replaying)} />
+            <a href={`/drawing/${roomCode}/${index}`} download={`${round.word}.png`}>Download</a>
+        </div>
+    );
+}
+
+export default function Gallery() {
+    const gallery = useSelector(state => state.room.gallery);
+    const users = useSelector(state => state.room.users);
+    const roomCode = useSelector(state => state.room.code);
+
+    if (!gallery || gallery.length === 0) {
+        return null;
+    }
+
+    return (
+        <div className="gallery">
+            <h2>Gallery:</h2>
+            {gallery.map((round, index) => (
+                <Drawing key={index} {...{ round, index, users, roomCode }} />
+            ))}
+        </div>
+    );
+}
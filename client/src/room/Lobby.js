@@ -20,6 +20,12 @@ function parseCustomWords(inputText) {
 	.filter(w => w.length > 0);
 }
 
+const CANVAS_PRESETS = {
+    square: { name: 'Square', width: 500, height: 500 },
+    wide: { name: 'Wide', width: 800, height: 450 },
+    portrait: { name: 'Portrait', width: 400, height: 700 },
+};
+
 function debounce(cb, timeout) {
     let timeout_id = null;
     return [e => {
@@ -41,9 +47,21 @@ function debounce(cb, timeout) {
 export default function Lobby({ socketManager }) {
     const [canStart, setCanStart] = useState(false);
     const wordpacks = useSelector(state => state.room.wordpacks);
+    const modes = useSelector(state => state.room.modes);
+    const [modeID, setModeID] = useState('0');
+    const users = useSelector(state => state.room.users);
+    const bots = useSelector(state => state.room.bots) || [];
+    const [teamCount, setTeamCount] = useState(2);
+    const [drawerCount, setDrawerCount] = useState(2);
+    const [clueRule, setClueRule] = useState('word');
+    // Players the host has put in a team, anyone else is auto-balanced
+    const [teamAssignments, setTeamAssignments] = useState({});
     const [selectedWordpacks, setSelectedWordpacks] = useState({});
     const roundTimerCheckboxRef = useRef(null);
     const canvasClearCheckboxRef = useRef(null);
+    const roundLimitRef = useRef(null);
+    const canvasPresetRef = useRef(null);
+    const maxPenSizeRef = useRef(null);
     const [customWords, setCustomWords] = useState("");
     const [parsedCustomWords, setParsedCustomWords] = useState([]);
     const [parseCallback, setParseCallback] = useState(() => {});
@@ -53,11 +71,40 @@ export default function Lobby({ socketManager }) {
         e.preventDefault();
         const timeLimit = roundTimerCheckboxRef.current.checked? 'T': 'F';
         const canvasClearing = canvasClearCheckboxRef.current.checked? 'T': 'F';
+        const roundLimit = parseInt(roundLimitRef.current.value) || 0;
+        const { width, height } = CANVAS_PRESETS[canvasPresetRef.current.value];
+        const maxPenSize = Math.min(Math.max(parseInt(maxPenSizeRef.current.value) || 10, 1), 50);
+        const assignments = Object.keys(teamAssignments)
+            .filter(id => users[id] && teamAssignments[id] < teamCount)
+            .map(id => `${id}=${teamAssignments[id]}`);
+        let modeOptions = '';
+        if (isTeams) {
+            modeOptions = [teamCount, ...assignments].join(',');
+        } else if (isCollaborative) {
+            modeOptions = drawerCount.toString();
+        } else if (isReverse) {
+            modeOptions = clueRule;
+        }
         const selectedIDs = Object.keys(selectedWordpacks).filter(id => selectedWordpacks[id]);
         const customWordPack = parseCustomWords(customWords).map(words => words.join(',')).join('|');
         if (canStart) {
-            socketManager.startGame(selectedIDs, timeLimit, canvasClearing, customWordPack);
+            socketManager.startGame(selectedIDs, timeLimit, canvasClearing, customWordPack, roundLimit, { width, height, maxPenSize }, modeID, modeOptions);
+        }
+    }
+
+    const selectedMode = (modes || []).find(mode => mode.id === modeID) || {};
+    const isTeams = selectedMode.name === 'Teams';
+    const isCollaborative = selectedMode.name === 'Collaborative';
+    const isReverse = selectedMode.name === 'Reverse';
+
+    const assignTeam = (id, team) => {
+        const assignments = { ...teamAssignments };
+        if (team === 'auto') {
+            delete assignments[id];
+        } else {
+            assignments[id] = parseInt(team);
         }
+        setTeamAssignments(assignments);
     }
 
     const customWordsChanged = e => {
@@ -93,6 +140,68 @@ export default function Lobby({ socketManager }) {
         <>
             <h2>Room Settings:</h2>
             <form className="start-form" onSubmit={onStart}>
+                {modes?
+                    <label className="game-mode" htmlFor="game-mode-select">Game mode
+                        <select id="game-mode-select" value={modeID} onChange={e => setModeID(e.target.value)}>
+                            {modes.map(mode => <option key={mode.id} value={mode.id}>{mode.name}</option>)}
+                        </select>
+                        <p>{selectedMode.description}</p>
+                    </label>
+                    : null}
+                {isTeams?
+                    <div className="team-settings">
+                        <label className="round-limit" htmlFor="team-count-input">Teams
+                            <input
+                                type="number"
+                                id="team-count-input"
+                                min="2"
+                                max="8"
+                                value={teamCount}
+                                onChange={e => setTeamCount(Math.min(Math.max(parseInt(e.target.value) || 2, 2), 8))} />
+                        </label>
+                        {Object.values(users).map(user => (
+                            <label key={user.id} className="team-assignment">{user.username}
+                                <select
+                                    value={teamAssignments[user.id] < teamCount? teamAssignments[user.id]: 'auto'}
+                                    onChange={e => assignTeam(user.id, e.target.value)}>
+                                    <option value="auto">Auto</option>
+                                    {[...Array(teamCount).keys()].map(team => (
+                                        <option key={team} value={team}>Team {team + 1}</option>
+                                    ))}
+                                </select>
+                            </label>
+                        ))}
+                    </div>
+                    : null}
+                {isCollaborative?
+                    <label className="round-limit" htmlFor="drawer-count-input">Players drawing each round
+                        <input
+                            type="number"
+                            id="drawer-count-input"
+                            min="2"
+                            max="4"
+                            value={drawerCount}
+                            onChange={e => setDrawerCount(Math.min(Math.max(parseInt(e.target.value) || 2, 2), 4))} />
+                    </label>
+                    : null}
+                {isReverse?
+                    <label className="game-mode" htmlFor="clue-rule-select">Clues can't use
+                        <select id="clue-rule-select" value={clueRule} onChange={e => setClueRule(e.target.value)}>
+                            <option value="word">The word</option>
+                            <option value="letters">Any letter in the word</option>
+                        </select>
+                    </label>
+                    : null}
+                <div className="bot-settings">
+                    <h2>Bots:</h2>
+                    {bots.filter(id => users[id]).map(id => (
+                        <div key={id} className="bot">
+                            {users[id].username}
+                            <button type="button" onClick={() => socketManager.removeBot(id)}>Remove</button>
+                        </div>
+                    ))}
+                    <button type="button" onClick={() => socketManager.addBot()}>Add bot</button>
+                </div>
                 <label className="checkbox-wrapper" htmlFor="round-timer-checkbox">Two minute round timer
                     <input type="checkbox" id="round-timer-checkbox" ref={roundTimerCheckboxRef} />
                     <span className="checkbox-span"></span>
@@ -101,6 +210,21 @@ export default function Lobby({ socketManager }) {
                     <input type="checkbox" id="canvas-clear-checkbox" ref={canvasClearCheckboxRef} />
                     <span className="checkbox-span"></span>
                 </label>
+                <label className="round-limit" htmlFor="round-limit-input">Rounds (0 for no limit)
+                    <input type="number" id="round-limit-input" min="0" defaultValue="0" ref={roundLimitRef} />
+                </label>
+                <label className="round-limit" htmlFor="canvas-preset-select">Canvas shape
+                    <select id="canvas-preset-select" defaultValue="square" ref={canvasPresetRef}>
+                        {Object.keys(CANVAS_PRESETS).map(key => (
+                            <option key={key} value={key}>
+                                {CANVAS_PRESETS[key].name} ({CANVAS_PRESETS[key].width}x{CANVAS_PRESETS[key].height})
+                            </option>
+                        ))}
+                    </select>
+                </label>
+                <label className="round-limit" htmlFor="max-pen-size-input">Biggest pen size
+                    <input type="number" id="max-pen-size-input" min="1" max="50" defaultValue="10" ref={maxPenSizeRef} />
+                </label>
                 <div>
                     <h2>Wordpacks:</h2>
                 </div>
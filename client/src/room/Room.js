@@ -4,6 +4,9 @@ import './Room.css';
 import Chat from './Chat';
 import Canvas from './Canvas';
 import Lobby from './Lobby';
+import Gallery from './Gallery';
+import { TelephoneWrite, Chains } from './Telephone';
+import { Voting, VoteResults } from './Voting';
 
 import { useSelector } from 'react-redux';
 
@@ -32,14 +35,38 @@ function Countdown({ timestamp }) {
     );
 }
 
+function Scoreboard({ teams, users }) {
+    return (
+        <div className="scoreboard">
+            {teams.map((team, index) => (
+                <div key={index} className="scoreboard-team">
+                    <span className="team-name">Team {index + 1}: </span>
+                    {team.points} point{team.points === 1? '': 's'}
+                    <span className="username-list">
+                        {' (' + team.members.filter(id => users[id]).map(id => users[id].username).join(', ') + ')'}
+                    </span>
+                </div>
+            ))}
+        </div>
+    );
+}
+
 export default function Room({ socketManager }) {
     const word = useSelector(state => state.room.word);
     const roomCode = useSelector(state => state.room.code);
-    const leader = useSelector(state => state.room.leader);
+    const leaders = useSelector(state => state.room.leaders);
     const roomState = useSelector(state => state.room.state);
     const host = useSelector(state => state.room.host);
     const winner = useSelector(state => state.room.winner);
     const timestamp = useSelector(state => state.room.timestamp);
+    const teams = useSelector(state => state.room.teams);
+    const users = useSelector(state => state.room.users);
+    const prompt = useSelector(state => state.room.prompt);
+    const waiting = useSelector(state => state.room.waiting);
+    const clueGiver = useSelector(state => state.room.clueGiver);
+    const judge = useSelector(state => state.room.judge);
+
+    const [showGallery, setShowGallery] = useState(false);
 
     const leaveRoomSubmit = e => {
         e.preventDefault();
@@ -48,6 +75,13 @@ export default function Room({ socketManager }) {
         socketManager.leaveRoom();
     };
 
+    const toggleGallery = () => {
+        if (!showGallery) {
+            socketManager.requestGallery();
+        }
+        setShowGallery(!showGallery);
+    };
+
     // roomState is sent shortly after joining room for first time
     if (!roomState) {
         return (<></>);
@@ -56,6 +90,11 @@ export default function Room({ socketManager }) {
     let showCanvas = true;
     let showLobby = false;
     let showCountdown = false;
+    let showTelephoneWrite = false;
+    let showVoting = false;
+
+    // Anyone drawing with the current user
+    const otherLeaders = (leaders || []).filter(user => !user.isCurrentUser).map(user => user.username);
 
     let title;
     switch (roomState) {
@@ -70,11 +109,15 @@ export default function Room({ socketManager }) {
             showCanvas = false;
             break;
         case 'leader':
-            title = (<h2 className="title">Draw {word}</h2>);
+            title = (<h2 className="title">Draw {word}{otherLeaders.length > 0? ` with ${otherLeaders.join(' and ')}`: ''}</h2>);
             showCountdown = true;
             break;
         case 'guesser':
-            title = (<h2 className="title">Guess what {leader.username} is drawing</h2>);
+            title = (
+                <h2 className="title">
+                    Guess what {leaders.map(user => user.username).join(' and ')} {leaders.length === 1? 'is': 'are'} drawing
+                </h2>
+            );
             showCountdown = true;
             break;
         case 'winner':
@@ -87,6 +130,59 @@ export default function Room({ socketManager }) {
         case 'timeout':
             title = (<h2 className="title">Time has run out! The word was: {word}</h2>)
             break;
+        case 'telephone-write':
+            title = (<h2 className="title">{prompt? 'Describe what was drawn': 'Write something for someone to draw'}</h2>);
+            showCanvas = false;
+            showTelephoneWrite = true;
+            showCountdown = true;
+            break;
+        case 'telephone-draw':
+            title = (
+                <h2 className="title">
+                    {prompt && prompt.text? `Draw ${prompt.text}`: 'Draw anything you like'}
+                    <input type="button" value="Done" onClick={() => socketManager.submitTelephone('')} />
+                </h2>
+            );
+            showCountdown = true;
+            break;
+        case 'telephone-wait':
+            title = (
+                <h2 className="title">
+                    Waiting for {(waiting || []).filter(id => users[id]).map(id => users[id].username).join(', ')}
+                </h2>
+            );
+            showCanvas = false;
+            break;
+        case 'clue-giver':
+            title = (<h2 className="title">Describe {word} in the chat for everyone to draw</h2>);
+            showCanvas = false;
+            showCountdown = true;
+            break;
+        case 'reverse-draw':
+            title = (
+                <h2 className="title">
+                    Draw what {clueGiver? clueGiver.username: 'someone who left'} is describing
+                    <input type="button" value="Done" onClick={() => socketManager.submitTelephone('')} />
+                </h2>
+            );
+            showCountdown = true;
+            break;
+        case 'voting':
+            if (!judge) {
+                title = (<h2 className="title">Vote for the best drawing of {word}</h2>);
+            } else if (judge.isCurrentUser) {
+                title = (<h2 className="title">Pick the drawing closest to {word}</h2>);
+            } else {
+                title = (<h2 className="title">Waiting for {judge.username} to pick the drawing closest to {word}</h2>);
+            }
+            showCanvas = false;
+            showVoting = true;
+            showCountdown = true;
+            break;
+        case 'vote-results':
+            title = (<h2 className="title">The results are in for {word}</h2>);
+            showCanvas = false;
+            break;
         default:
     }
 
@@ -97,17 +193,28 @@ export default function Room({ socketManager }) {
                     <div className="room-status-bar">
                         <h2>In room {roomCode}</h2>
                         <form className="leave-form" onSubmit={leaveRoomSubmit}>
+                            <input type="button" value={showGallery? 'Hide Gallery': 'Gallery'} onClick={toggleGallery} />
                             <input type="submit" value="Leave Room" />
                         </form>
                     </div>
                     {showCountdown? <Countdown timestamp={timestamp}/>: null}
+                    {teams && teams.length > 0? <Scoreboard teams={teams} users={users} />: null}
                     {title}
                     <div className="canvas-hider" hidden={!showCanvas}>
-                        <Canvas socketManager={socketManager} isLeader={roomState === 'leader'} />
+                        <Canvas socketManager={socketManager} isLeader={['leader', 'telephone-draw', 'reverse-draw'].includes(roomState)} />
                     </div>
+                    {showTelephoneWrite? <TelephoneWrite socketManager={socketManager} />: null}
+                    {showVoting? <Voting socketManager={socketManager} />: null}
+                    {roomState === 'vote-results'? <VoteResults />: null}
                     <div hidden={!showLobby}>
                         <Lobby socketManager={socketManager} />
                     </div>
+                    <div hidden={roomState !== 'lobby'}>
+                        <Chains />
+                    </div>
+                    <div hidden={!showGallery && roomState !== 'lobby'}>
+                        <Gallery />
+                    </div>
                 </div>
                 <div className="chat-card">
                     <Chat socketManager={socketManager} disabled={roomState === 'leader'} />
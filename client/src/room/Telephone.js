@@ -0,0 +1,93 @@
+import React, { useEffect, useRef, useState } from 'react';
+import { useSelector } from 'react-redux';
+
+import { renderCommand } from './drawing';
+
+const DEFAULT_CANVAS = { width: 500, height: 500 };
+
+export function StaticDrawing({ commands, width, height }) {
+    const canvasRef = useRef(null);
+
+    useEffect(() => {
+        const context = canvasRef.current.getContext('2d');
+        context.clearRect(0, 0, width, height);
+        commands.forEach(command => renderCommand(context, command));
+    }, [commands, width, height]);
+
+    return (<canvas ref={canvasRef} width={width} height={height}></canvas>);
+}
+
+export function TelephoneWrite({ socketManager }) {
+    const prompt = useSelector(state => state.room.prompt);
+    const canvas = useSelector(state => state.room.canvas) || DEFAULT_CANVAS;
+    const [text, setText] = useState('');
+
+    const submit = e => {
+        e.preventDefault();
+        if (text.trim() !== '') {
+            socketManager.submitTelephone(text.trim());
+            setText('');
+        }
+    };
+
+    let shown = null;
+    if (prompt && prompt.commands) {
+        shown = (<StaticDrawing commands={prompt.commands} width={canvas.width} height={canvas.height} />);
+    } else if (prompt) {
+        shown = (<p className="telephone-prompt">{prompt.text}</p>);
+    }
+
+    return (
+        <div className="telephone-write">
+            {shown}
+            <form className="telephone-form" onSubmit={submit}>
+                <input
+                    type="text"
+                    maxLength="100"
+                    value={text}
+                    placeholder={prompt? 'Describe this': 'Write something to draw'}
+                    onChange={e => setText(e.target.value)} />
+                <input type="submit" value="Done" disabled={text.trim() === ''} />
+            </form>
+        </div>
+    );
+}
+
+export function Chains() {
+    const chains = useSelector(state => state.room.chains);
+    const gallery = useSelector(state => state.room.gallery);
+    const users = useSelector(state => state.room.users);
+
+    if (!chains || chains.length === 0) {
+        return null;
+    }
+
+    const username = id => users[id]? users[id].username: 'someone who left';
+
+    return (
+        <div className="chains">
+            <h2>Chains:</h2>
+            {chains.map((chain, index) => (
+                <div key={index} className="chain">
+                    {chain.map((entry, step) => {
+                        let content;
+                        if (entry.text !== undefined) {
+                            content = (<div className="chain-text">{entry.text}</div>);
+                        } else if (entry.drawing !== undefined && gallery && gallery[entry.drawing]) {
+                            const round = gallery[entry.drawing];
+                            content = (<StaticDrawing commands={round.commands} width={round.width} height={round.height} />);
+                        } else {
+                            content = (<div className="chain-text skipped">Skipped</div>);
+                        }
+                        return (
+                            <div key={step} className="chain-entry">
+                                {content}
+                                <div className="chain-author">{username(entry.author)}</div>
+                            </div>
+                        );
+                    })}
+                </div>
+            ))}
+        </div>
+    );
+}
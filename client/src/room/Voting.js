@@ -0,0 +1,62 @@
+import React, { useState, useEffect } from 'react';
+import { useSelector } from 'react-redux';
+
+import { StaticDrawing } from './Telephone';
+
+const DEFAULT_CANVAS = { width: 500, height: 500 };
+
+export function Voting({ socketManager }) {
+    const drawings = useSelector(state => state.room.drawings) || [];
+    const canvas = useSelector(state => state.room.canvas) || DEFAULT_CANVAS;
+    const socketID = useSelector(state => state.socketID);
+    const judge = useSelector(state => state.room.judge);
+    // Only the judge can pick a drawing when there is one
+    const canVote = !judge || judge.isCurrentUser;
+    const [votedFor, setVotedFor] = useState(null);
+
+    // Everyone gets a fresh vote for each round
+    useEffect(() => {
+        setVotedFor(null);
+    }, [drawings]);
+
+    const vote = author => {
+        socketManager.vote(author);
+        setVotedFor(author);
+    };
+
+    return (
+        <div className="voting">
+            {drawings.map(drawing => (
+                <div key={drawing.author} className={'voting-entry' + (drawing.author === votedFor? ' voted': '')}>
+                    <StaticDrawing commands={drawing.commands} width={canvas.width} height={canvas.height} />
+                    <input
+                        type="button"
+                        value={drawing.author === socketID? 'Your drawing': 'Vote'}
+                        disabled={drawing.author === socketID || votedFor !== null || !canVote}
+                        onClick={() => vote(drawing.author)} />
+                </div>
+            ))}
+        </div>
+    );
+}
+
+export function VoteResults() {
+    const results = useSelector(state => state.room.voteResults) || [];
+    const users = useSelector(state => state.room.users);
+
+    if (results.length === 0) {
+        return (<p className="vote-results">Not enough drawings were handed in to vote on</p>);
+    }
+
+    const username = id => users[id]? users[id].username: 'someone who left';
+
+    return (
+        <ol className="vote-results">
+            {results.map(result => (
+                <li key={result.author}>
+                    {username(result.author)}: {result.votes} vote{result.votes === 1? '': 's'} ({result.points} point{result.points === 1? '': 's'})
+                </li>
+            ))}
+        </ol>
+    );
+}
@@ -0,0 +1,65 @@
+// This must be kept in sync with the palette in the server
+export const PALETTE = [
+    '#000000', '#ffffff', '#808080', '#e53935', '#fb8c00', '#fdd835',
+    '#43a047', '#039be5', '#1e35e5', '#8e24aa', '#f06292', '#6d4c41',
+];
+
+export function renderLine(context, startX, startY, endX, endY, penSize, colour, erase) {
+    // Erasing cuts the line out of the canvas rather than drawing over it
+    context.globalCompositeOperation = erase? 'destination-out': 'source-over';
+    context.fillStyle = colour;
+    context.strokeStyle = colour;
+
+    context.beginPath();
+    context.ellipse(startX, startY, penSize, penSize, 0, 0, 2 * Math.PI);
+    context.fill();
+
+    context.beginPath();
+    context.lineWidth = penSize * 2;
+    context.moveTo(endX, endY);
+    context.lineTo(startX, startY);
+    context.stroke();
+    context.closePath();
+
+    context.globalCompositeOperation = 'source-over';
+}
+
+export function floodFill(context, startX, startY, colour) {
+    const { width, height } = context.canvas;
+    startX = Math.min(startX, width - 1);
+    startY = Math.min(startY, height - 1);
+    const image = context.getImageData(0, 0, width, height);
+    const data = image.data;
+    const start = (startY * width + startX) * 4;
+    const target = data.slice(start, start + 4);
+    const fill = [1, 3, 5].map(i => parseInt(colour.slice(i, i + 2), 16)).concat([255]);
+    if (fill.every((value, i) => value === target[i])) {
+        return;
+    }
+
+    const matches = i => target.every((value, j) => data[i + j] === value);
+    const stack = [[startX, startY]];
+    while (stack.length > 0) {
+        const [x, y] = stack.pop();
+        if (x < 0 || y < 0 || x >= width || y >= height) continue;
+        const i = (y * width + x) * 4;
+        if (!matches(i)) continue;
+        fill.forEach((value, j) => { data[i + j] = value; });
+        stack.push([x + 1, y], [x - 1, y], [x, y + 1], [x, y - 1]);
+    }
+    context.putImageData(image, 0, 0);
+}
+
+// Renders a draw command as parsed by the socket manager
+export function renderCommand(context, [type, , ...params]) {
+    if (type === 'draw') {
+        const [startX, startY, endX, endY, penSize, colour] = params;
+        renderLine(context, startX, startY, endX, endY, penSize, PALETTE[colour] || PALETTE[0], false);
+    } else if (type === 'erase') {
+        const [startX, startY, endX, endY, penSize] = params;
+        renderLine(context, startX, startY, endX, endY, penSize, PALETTE[0], true);
+    } else if (type === 'fill') {
+        const [x, y, colour] = params;
+        floodFill(context, x, y, PALETTE[colour] || PALETTE[0]);
+    }
+}
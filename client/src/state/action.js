@@ -26,6 +26,10 @@ export function chatMessage(userID, content) {
     return { type: 'CHAT_MESSAGE', message: { userID, content } };
 }
 
+export function messageBlocked(content) {
+    return { type: 'MESSAGE_BLOCKED', content };
+}
+
 export function winner(winnerID, points, rawWord, rawAlternate) {
     const word = rawWord[0].toUpperCase() + rawWord.slice(1);
     let alternate;
@@ -40,7 +44,7 @@ export function timeout(rawWord) {
     return { type: 'TIMEOUT', word };
 }
 
-export function becomeLeader(canvasClearing, rawWord, rawTimeout) {
+export function becomeLeader(canvasClearing, rawWord, rawTimeout, otherLeaderIDs) {
     let timeout;
     if (rawTimeout === '0') {
         timeout = null;
@@ -48,17 +52,17 @@ export function becomeLeader(canvasClearing, rawWord, rawTimeout) {
         timeout = new Date(Number(rawTimeout));
     }
     const word = rawWord[0].toUpperCase() + rawWord.slice(1);
-    return { type: 'BECOME_LEADER', canvasClearing, word, timeout };
+    return { type: 'BECOME_LEADER', canvasClearing, word, timeout, otherLeaderIDs };
 }
 
-export function becomeGuesser(leaderID, rawTimeout) {
+export function becomeGuesser(leaderIDs, rawTimeout) {
     let timeout;
     if (rawTimeout === '0') {
         timeout = null;
     } else {
         timeout = new Date(Number(rawTimeout));
     }
-    return { type: 'BECOME_GUESSER', leaderID, timeout };
+    return { type: 'BECOME_GUESSER', leaderIDs, timeout };
 }
 
 export function userJoinedRoom(userID, username) {
@@ -68,3 +72,70 @@ export function userJoinedRoom(userID, username) {
 export function userLeftRoom(userID) {
     return { type: 'USER_LEFT', userID };
 }
+
+export function receiveGallery(rounds) {
+    return { type: 'RECEIVE_GALLERY', rounds };
+}
+
+export function receiveCanvasSettings(width, height, maxPenSize) {
+    return { type: 'RECEIVE_CANVAS_SETTINGS', canvas: { width, height, maxPenSize } };
+}
+
+export function receiveGameModes(modes) {
+    return { type: 'RECEIVE_GAME_MODES', modes };
+}
+
+export function receiveScoreboard(teams) {
+    return { type: 'RECEIVE_SCOREBOARD', teams };
+}
+
+export function telephoneStep(drawing, prompt, rawTimeout) {
+    let timeout;
+    if (rawTimeout === '0') {
+        timeout = null;
+    } else {
+        timeout = new Date(Number(rawTimeout));
+    }
+    return { type: 'TELEPHONE_STEP', drawing, prompt, timeout };
+}
+
+export function telephoneWaiting(waiting) {
+    return { type: 'TELEPHONE_WAITING', waiting };
+}
+
+export function receiveChains(chains) {
+    return { type: 'RECEIVE_CHAINS', chains };
+}
+
+export function startVoting(word, drawings, rawTimeout, judgeID) {
+    let timeout;
+    if (rawTimeout === '0') {
+        timeout = null;
+    } else {
+        timeout = new Date(Number(rawTimeout));
+    }
+    return { type: 'START_VOTING', word, drawings, timeout, judgeID };
+}
+
+export function reverseStep(clueGiverID, rawWord, rawTimeout) {
+    let timeout;
+    if (rawTimeout === '0') {
+        timeout = null;
+    } else {
+        timeout = new Date(Number(rawTimeout));
+    }
+    const word = rawWord? rawWord[0].toUpperCase() + rawWord.slice(1): null;
+    return { type: 'REVERSE_STEP', clueGiverID, word, timeout };
+}
+
+export function receiveBots(botIDs) {
+    return { type: 'RECEIVE_BOTS', botIDs };
+}
+
+export function serverRestarting(timestamp) {
+    return { type: 'SERVER_RESTARTING', timestamp };
+}
+
+export function receiveVoteResults(word, results) {
+    return { type: 'VOTE_RESULTS', word, results };
+}
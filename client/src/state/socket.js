@@ -12,8 +12,46 @@ import {
     userLeftRoom,
     winner,
     timeout,
+    receiveGallery,
+    receiveCanvasSettings,
+    receiveGameModes,
+    receiveScoreboard,
+    telephoneStep,
+    telephoneWaiting,
+    receiveChains,
+    startVoting,
+    receiveVoteResults,
+    reverseStep,
+    receiveBots,
+    serverRestarting,
+    messageBlocked,
 } from './action';
 
+// Draw commands are stored as arrays of [type, strokeKey, ...params] where params are:
+//   draw:  startX, startY, endX, endY, penSize, colour
+//   erase: startX, startY, endX, endY, penSize
+//   fill:  x, y, colour
+// Stroke ids are only unique among each author's strokes, so strokes are keyed by
+// both as author:id
+export function strokeKey(author, strokeID) {
+    return author + ':' + strokeID;
+}
+
+function parseDrawCommand(message) {
+    // The author is last, and is kept as a string since session ids are too big to
+    // be numbers
+    let parts = message.slice(1).split(',');
+    let author = parts.pop();
+    let p = parts.map(str => parseInt(str));
+    if (message[0] === 'd') {
+        return ['draw', strokeKey(author, p[5]), ...p.slice(0, 5), p[6]];
+    } else if (message[0] === 'x') {
+        return ['erase', strokeKey(author, p[5]), ...p.slice(0, 5)];
+    } else {
+        return ['fill', strokeKey(author, p[2]), p[0], p[1], p[3]];
+    }
+}
+
 export default class SocketManager {
     constructor(url, store) {
         this.url = url;
@@ -23,6 +61,7 @@ export default class SocketManager {
         this.drawBuffer = [];
         this.newRoundHandler = null;
         this.joinRoomErrorHandler = null;
+        this.createRoomErrorHandler = null;
     }
 
     connect() {
@@ -34,22 +73,50 @@ export default class SocketManager {
     handleSocketMessage(e) {
         let message = e.data;
         let i;
-        if (message[0] === 'd') {
-            let p = message
-                .slice(1)
-                .split(',')
-                .map(str => parseInt(str));
-            if (this.drawHandler) {
-                this.drawHandler.apply(undefined, [false, ...p]);
-            } else {
-                this.drawBuffer.push([false, ...p]);
-            }
+        if (message[0] === 'd' || message[0] === 'x' || message[0] === 'p') {
+            this.handleDrawCommand(parseDrawCommand(message));
+        } else if (message[0] === 'h') {
+            let commands = message.split('\n').slice(1).map(parseDrawCommand);
+            this.handleDrawCommand(['snapshot', commands]);
         } else if (message[0] === 'b') {
-            if (this.drawHandler) {
-                this.drawHandler(true);
-            } else {
-                this.drawBuffer.push([true]);
-            }
+            this.handleDrawCommand(['clear']);
+        } else if (message[0] === 'z') {
+            let [author, strokeID] = message.slice(1).split(',');
+            this.handleDrawCommand(['undo', strokeKey(author, strokeID)]);
+        } else if (message[0] === 'y') {
+            // The first line is the author and stroke id, every other line is a draw
+            // command
+            let lines = message.slice(1).split('\n');
+            let [author, strokeID] = lines.shift().split(',');
+            this.handleDrawCommand(['redo', strokeKey(author, strokeID), lines.map(parseDrawCommand)]);
+        } else if (message[0] === 'a') {
+            // Each round starts with a line of the leader, winner, canvas size and word
+            // followed by its draw commands
+            let rounds = [];
+            message.split('\n').slice(1).forEach(line => {
+                if (line[0] === '#') {
+                    let parts = line.slice(1).split(',');
+                    rounds.push({
+                        leaderID: parts[0],
+                        winnerID: parts[1] === ''? null: parts[1],
+                        width: parseInt(parts[2]),
+                        height: parseInt(parts[3]),
+                        word: parts.slice(4).join(','),
+                        commands: [],
+                        times: [],
+                    });
+                } else if (rounds.length > 0) {
+                    // Draw commands are prefixed with when they were drawn
+                    let separator = line.indexOf(':');
+                    let round = rounds[rounds.length - 1];
+                    round.times.push(parseInt(line.slice(0, separator)));
+                    round.commands.push(parseDrawCommand(line.slice(separator + 1)));
+                }
+            });
+            this.store.dispatch(receiveGallery(rounds));
+        } else if (message[0] === 'k') {
+            let [width, height, maxPenSize] = message.slice(1).split(',').map(x => parseInt(x));
+            this.store.dispatch(receiveCanvasSettings(width, height, maxPenSize));
         } else if (message[0] === 'c') {
             let id = message.slice(1);
             this.store.dispatch(socketConnected(id));
@@ -57,15 +124,17 @@ export default class SocketManager {
             message = message.slice(1).split(',');
             this.store.dispatch(chatMessage(message[0], message[1]));
         } else if (message[0] === 'l') {
+            // Anyone else drawing follows the timeout
             let canvasClearing = message[1] === 'T';
             let parts = message.slice(2).split(',');
             this.store.dispatch(
-                becomeLeader(canvasClearing, parts[0], parts[1])
+                becomeLeader(canvasClearing, parts[0], parts[1], parts.slice(2))
             );
             if (this.newRoundHandler) this.newRoundHandler();
         } else if (message[0] === 'r') {
+            // The timeout is followed by everyone drawing
             let parts = message.slice(1).split(',');
-            this.store.dispatch(becomeGuesser(parts[0], parts[1]));
+            this.store.dispatch(becomeGuesser(parts.slice(1), parts[0]));
             if (this.newRoundHandler) this.newRoundHandler();
         } else if (message[0] === 'e') {
             let parts = message.slice(1).split(',');
@@ -78,6 +147,9 @@ export default class SocketManager {
                 };
             }
             this.store.dispatch(joinedRoom(code, users));
+        } else if (message[0] === 'B') {
+            let botIDs = message.slice(1).split(',').filter(id => id.length > 0);
+            this.store.dispatch(receiveBots(botIDs));
         } else if (message[0] === 'j') {
             let userJoinParts = message.slice(1).split(',');
             this.store.dispatch(
@@ -113,17 +185,125 @@ export default class SocketManager {
                 });
             }
             this.store.dispatch(receiveSettingsData(wordpacks));
+        } else if (message[0] === 't') {
+            // Each line is a team's points followed by its members
+            let teams = message.split('\n').slice(1).map(line => {
+                let parts = line.split(',');
+                return { points: parseInt(parts[0]), members: parts.slice(1) };
+            });
+            this.store.dispatch(receiveScoreboard(teams));
+        } else if (message[0] === 'i') {
+            if (message[1] === 'P') {
+                let waiting = message.length > 2? message.slice(2).split(','): [];
+                this.store.dispatch(telephoneWaiting(waiting));
+            } else if (message[1] === 'C') {
+                // The first line has the timeout and who is describing the word, which
+                // follows on the next line if it's this user
+                let lines = message.split('\n');
+                let [timeout, clueGiverID] = lines[0].slice(2).split(',');
+                let word = lines.length > 1? lines[1].slice(1): null;
+                this.store.dispatch(reverseStep(clueGiverID, word, timeout));
+                if (this.newRoundHandler) this.newRoundHandler();
+            } else {
+                // The first line has the kind of step and timeout, what to draw or
+                // describe follows as text or draw commands
+                let lines = message.split('\n');
+                let drawing = lines[0][1] === 'D';
+                let prompt = null;
+                if (lines.length > 1 && lines[1][0] === 'T') {
+                    prompt = { text: lines[1].slice(1) };
+                } else if (lines.length > 1) {
+                    prompt = { commands: lines.slice(2).map(parseDrawCommand) };
+                }
+                this.store.dispatch(telephoneStep(drawing, prompt, lines[0].slice(2)));
+                if (drawing && this.newRoundHandler) this.newRoundHandler();
+            }
+        } else if (message[0] === 'u') {
+            // Each chain starts with a # line followed by its entries
+            let chains = [];
+            message.split('\n').slice(1).forEach(line => {
+                if (line[0] === '#') {
+                    chains.push([]);
+                } else if (chains.length > 0) {
+                    let separator = line.indexOf(',');
+                    let author = separator === -1? line.slice(1): line.slice(1, separator);
+                    let entry = { author };
+                    if (line[0] === 'T') {
+                        entry.text = line.slice(separator + 1);
+                    } else if (line[0] === 'D') {
+                        entry.drawing = parseInt(line.slice(separator + 1));
+                    }
+                    chains[chains.length - 1].push(entry);
+                }
+            });
+            this.store.dispatch(receiveChains(chains));
+        } else if (message[0] === 'n') {
+            let lines = message.split('\n');
+            if (message[1] === 'V' || message[1] === 'J') {
+                // Each drawing starts with a # line with its author followed by its
+                // draw commands. When judging the judge comes before the word.
+                let separator = lines[0].indexOf(',');
+                let judgeID = null;
+                if (message[1] === 'J') {
+                    let judgeSeparator = lines[0].indexOf(',', separator + 1);
+                    judgeID = lines[0].slice(separator + 1, judgeSeparator);
+                    separator = judgeSeparator;
+                }
+                let drawings = [];
+                lines.slice(1).forEach(line => {
+                    if (line[0] === '#') {
+                        drawings.push({ author: line.slice(1), commands: [] });
+                    } else if (drawings.length > 0) {
+                        drawings[drawings.length - 1].commands.push(parseDrawCommand(line));
+                    }
+                });
+                this.store.dispatch(startVoting(
+                    lines[0].slice(separator + 1),
+                    drawings,
+                    lines[0].slice(2, lines[0].indexOf(',')),
+                    judgeID,
+                ));
+            } else {
+                let results = lines.slice(1).map(line => {
+                    let parts = line.split(',');
+                    return { author: parts[0], votes: parseInt(parts[1]), points: parseInt(parts[2]) };
+                });
+                this.store.dispatch(receiveVoteResults(lines[0].slice(2), results));
+            }
+        } else if (message[0] === 'v') {
+            let modes = message.split('\n').slice(1).map(line => {
+                let parts = line.split(',');
+                return { id: parts[0], name: parts[1], description: parts.slice(2).join(',') };
+            });
+            this.store.dispatch(receiveGameModes(modes));
         } else if (message[0] === 'f') {
-            if (message[1] === 'u') {
+            if (message[1] === 'c') {
+                let comma = message.indexOf(',');
+                this.createRoomErrorHandler(message.slice(2, comma), message.slice(comma + 1));
+            } else if (message[1] === 'b') {
+                this.store.dispatch(messageBlocked(message.slice(2)));
+            } else if (message[1] === 'u') {
                 this.joinRoomErrorHandler(null, message.slice(2));
             } else {
                 this.joinRoomErrorHandler(message.slice(2), null);
             }
+        } else if (message[0] === 'R') {
+            // The server closes the connection once it's restarting, and the socket
+            // reconnects to it when it's back
+            this.store.dispatch(serverRestarting(parseInt(message.slice(1))));
         } else {
             console.log(message);
         }
     }
 
+    handleDrawCommand(command) {
+        if (this.drawHandler) {
+            this.drawHandler.apply(undefined, command);
+        } else {
+            this.drawBuffer.push(command);
+        }
+    }
+
     onClose() {
         this.store.dispatch(socketDisconnected());
         setTimeout(() => {
@@ -149,6 +329,10 @@ export default class SocketManager {
         this.joinRoomErrorHandler = callback;
     }
 
+    setCreateRoomErrorHandler(callback) {
+        this.createRoomErrorHandler = callback;
+    }
+
     sendChat(message) {
         this.socket.send('m' + message);
     }
@@ -161,19 +345,55 @@ export default class SocketManager {
         this.socket.send('q');
     }
 
-    createRoom(username) {
-        this.socket.send('n' + username);
+    createRoom(username, vanityCode) {
+        this.socket.send('n' + username + (vanityCode? ',' + vanityCode: ''));
     }
 
-    sendDraw(params) {
-        this.socket.send('d' + params.join(','));
+    sendDrawCommand([type, strokeID, ...params]) {
+        if (type === 'draw') {
+            let [startX, startY, endX, endY, penSize, colour] = params;
+            this.socket.send('d' + [startX, startY, endX, endY, penSize, strokeID, colour].join(','));
+        } else if (type === 'erase') {
+            this.socket.send('x' + [...params, strokeID].join(','));
+        } else if (type === 'fill') {
+            let [x, y, colour] = params;
+            this.socket.send('p' + [x, y, strokeID, colour].join(','));
+        }
     }
 
     clear() {
         this.socket.send('c');
     }
 
-    startGame(selectedWordpackIDs, timeLimit, canvasClearing, customWordPack) {
+    undo() {
+        this.socket.send('z');
+    }
+
+    redo() {
+        this.socket.send('y');
+    }
+
+    submitTelephone(text) {
+        this.socket.send('u' + text);
+    }
+
+    vote(author) {
+        this.socket.send('v' + author);
+    }
+
+    addBot() {
+        this.socket.send('b+');
+    }
+
+    removeBot(botID) {
+        this.socket.send('b-' + botID);
+    }
+
+    requestGallery() {
+        this.socket.send('a');
+    }
+
+    startGame(selectedWordpackIDs, timeLimit, canvasClearing, customWordPack, roundLimit, canvas, mode, modeOptions) {
         this.socket.send(
             [
                 's',
@@ -181,6 +401,12 @@ export default class SocketManager {
                 timeLimit,
                 canvasClearing,
                 ' ' + (customWordPack || ''),
+                roundLimit || 0,
+                canvas.width,
+                canvas.height,
+                canvas.maxPenSize,
+                mode || 0,
+                modeOptions || '',
             ].join('\n')
         );
     }
@@ -44,6 +44,12 @@ function stateManager(state = {}, action) {
             const chatMessage = { type: 'chat', user: state.room.users[action.message.userID], content: action.message.content };
             newState.room.messages = pushItem(newState.room.messages, chatMessage);
             return newState;
+        case 'MESSAGE_BLOCKED':
+            newState = { ...state };
+            newState.room = { ...newState.room };
+            const blockedMessage = { type: 'blocked', content: action.content };
+            newState.room.messages = pushItem(newState.room.messages, blockedMessage);
+            return newState;
         case 'WINNER':
             newState = { ...state };
             newState.room = { ...newState.room };
@@ -54,7 +60,9 @@ function stateManager(state = {}, action) {
                 winner: state.room.users[action.winnerID],
                 points: action.points,
                 word: action.word,
-                alternate: action.alternate
+                alternate: action.alternate,
+                // In team games the points are the winner's team's
+                team: (state.room.teams || []).findIndex(team => team.members.includes(action.winnerID)),
             };
             newState.room.messages = pushItem(newState.room.messages, winMessage);
             return newState;
@@ -73,16 +81,22 @@ function stateManager(state = {}, action) {
             newState.room.state = 'leader';
             newState.room.word = action.word;
             newState.room.canvasClearing = action.canvasClearing;
-            newState.room.leader = state.room.users[state.socketID];
+            newState.room.leaders = [state.socketID, ...action.otherLeaderIDs]
+                .map(id => state.room.users[id])
+                .filter(user => user);
             newState.room.timestamp = action.timeout;
+            newState.room.chains = null;
             return newState;
         case 'BECOME_GUESSER':
-            console.debug('Became guesser leaderid =', action.leaderID);
+            console.debug('Became guesser leaderids =', action.leaderIDs);
             newState = { ...state };
             newState.room = { ...newState.room };
             newState.room.state = 'guesser';
-            newState.room.leader = state.room.users[action.leaderID];
+            newState.room.leaders = action.leaderIDs
+                .map(id => state.room.users[id])
+                .filter(user => user);
             newState.room.timestamp = action.timeout;
+            newState.room.chains = null;
             return newState;
         case 'USER_JOINED':
             newState = { ...state };
@@ -106,6 +120,86 @@ function stateManager(state = {}, action) {
             const leaveMessage = { type: 'user_left', user: state.room.users[action.userID] };
             newState.room.messages = pushItem(newState.room.messages, leaveMessage);
             return newState;
+        case 'RECEIVE_GALLERY':
+            newState = { ...state };
+            newState.room = { ...state.room };
+            newState.room.gallery = action.rounds;
+            return newState;
+        case 'TELEPHONE_STEP':
+            newState = { ...state };
+            newState.room = { ...state.room };
+            newState.room.state = action.drawing? 'telephone-draw': 'telephone-write';
+            newState.room.prompt = action.prompt;
+            newState.room.timestamp = action.timeout;
+            newState.room.waiting = null;
+            newState.room.chains = null;
+            return newState;
+        case 'SERVER_RESTARTING':
+            console.debug('Server restarting at', action.timestamp);
+            return { ...state, restartingAt: action.timestamp };
+        case 'RECEIVE_BOTS':
+            newState = { ...state };
+            newState.room = { ...state.room };
+            newState.room.bots = action.botIDs;
+            return newState;
+        case 'TELEPHONE_WAITING':
+            newState = { ...state };
+            newState.room = { ...state.room };
+            newState.room.waiting = action.waiting;
+            // Anyone who isn't being waited on has finished the step or joined late,
+            // voters keep seeing the drawings until everyone has voted and whoever
+            // describes the word keeps describing it
+            if (!action.waiting.includes(state.socketID) && !['voting', 'clue-giver'].includes(state.room.state)) {
+                newState.room.state = 'telephone-wait';
+            }
+            return newState;
+        case 'START_VOTING':
+            newState = { ...state };
+            newState.room = { ...state.room };
+            newState.room.state = 'voting';
+            newState.room.word = action.word;
+            newState.room.drawings = action.drawings;
+            newState.room.judge = action.judgeID? state.room.users[action.judgeID]: null;
+            newState.room.timestamp = action.timeout;
+            newState.room.waiting = null;
+            return newState;
+        case 'REVERSE_STEP':
+            newState = { ...state };
+            newState.room = { ...state.room };
+            newState.room.state = action.word? 'clue-giver': 'reverse-draw';
+            newState.room.word = action.word;
+            newState.room.clueGiver = state.room.users[action.clueGiverID];
+            newState.room.timestamp = action.timeout;
+            newState.room.waiting = null;
+            newState.room.chains = null;
+            return newState;
+        case 'VOTE_RESULTS':
+            newState = { ...state };
+            newState.room = { ...state.room };
+            newState.room.state = 'vote-results';
+            newState.room.word = action.word;
+            newState.room.voteResults = action.results;
+            return newState;
+        case 'RECEIVE_CHAINS':
+            newState = { ...state };
+            newState.room = { ...state.room };
+            newState.room.chains = action.chains;
+            return newState;
+        case 'RECEIVE_SCOREBOARD':
+            newState = { ...state };
+            newState.room = { ...state.room };
+            newState.room.teams = action.teams;
+            return newState;
+        case 'RECEIVE_GAME_MODES':
+            newState = { ...state };
+            newState.room = { ...state.room };
+            newState.room.modes = action.modes;
+            return newState;
+        case 'RECEIVE_CANVAS_SETTINGS':
+            newState = { ...state };
+            newState.room = { ...state.room };
+            newState.room.canvas = action.canvas;
+            return newState;
         default:
             console.debug('Unhandled action in state', action, 'state was:', state);
             return state;
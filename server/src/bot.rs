@@ -0,0 +1,313 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use actix::prelude::*;
+use log::trace;
+use rand::{prelude::*, rngs::StdRng};
+
+use crate::draw_history::{CanvasSettings, Tool};
+use crate::game_mode::Prompt;
+use crate::room::RoundRecord;
+use crate::server::*;
+
+/// How long a bot waits between guesses, in seconds
+const GUESS_DELAY: (u64, u64) = (8, 20);
+/// How long a bot takes to write, hand in a blank drawing or vote, in seconds
+const THINK_DELAY: (u64, u64) = (5, 15);
+/// How long a bot waits before giving up on a word it has never seen drawn
+const GIVE_UP_DELAY: Duration = Duration::from_secs(3);
+
+/// An occupant played by the server. It hears the same events as a session and
+/// answers by sending the same messages a client would, drawing the word from
+/// someone else's drawing of it in an earlier game and guessing words from the
+/// room's word packs.
+pub struct Bot {
+    id: usize,
+    room: String,
+    game_server: Addr<GameServer>,
+    rng: StdRng,
+    canvas: CanvasSettings,
+    /// Every word the room can pick from, fetched when the bot first guesses in a game
+    words: Vec<String>,
+    /// Goes up whenever the room moves on so that anything the bot planned to do
+    /// before then is dropped
+    turn: usize,
+    /// Muted bots don't do anything, used when replaying a recording which already
+    /// has everything the bot sent
+    muted: bool,
+}
+
+impl Bot {
+    pub fn new(id: usize, room: String, game_server: Addr<GameServer>, seed: u64) -> Bot {
+        Bot {
+            id,
+            room,
+            game_server,
+            rng: StdRng::seed_from_u64(seed),
+            canvas: CanvasSettings::default(),
+            words: Vec::new(),
+            turn: 0,
+            muted: false,
+        }
+    }
+
+    pub fn muted(mut self) -> Bot {
+        self.muted = true;
+        self
+    }
+
+    fn send(&self, content: String) {
+        self.game_server.do_send(ClientMessage {
+            session_id: self.id,
+            content,
+            room: Some(self.room.clone()),
+        });
+    }
+
+    fn random_delay(&mut self, (low, high): (u64, u64)) -> Duration {
+        Duration::from_secs(self.rng.gen_range(low, high))
+    }
+
+    /// Sends the message after the delay, unless the room has moved on by then
+    fn send_later(&mut self, delay: Duration, content: String, ctx: &mut Context<Self>) {
+        let turn = self.turn;
+        ctx.run_later(delay, move |act, _| {
+            if act.turn == turn {
+                act.send(content);
+            }
+        });
+    }
+
+    /// Runs `then` with the room's words, asking the server for them if the bot
+    /// doesn't have them yet
+    fn with_words<F>(&mut self, ctx: &mut Context<Self>, then: F)
+    where
+        F: FnOnce(&mut Bot, &mut Context<Self>) + 'static,
+    {
+        if !self.words.is_empty() {
+            then(self, ctx);
+            return;
+        }
+        let turn = self.turn;
+        self.game_server
+            .send(WordListRequest {
+                room: self.room.clone(),
+            })
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                if let Ok(words) = res {
+                    act.words = words;
+                }
+                if act.turn == turn {
+                    then(act, ctx);
+                }
+                fut::ready(())
+            })
+            .spawn(ctx);
+    }
+
+    fn random_word(&mut self) -> Option<String> {
+        self.words.choose(&mut self.rng).cloned()
+    }
+
+    /// Keeps guessing until the round is over
+    fn guess(&mut self, ctx: &mut Context<Self>) {
+        let delay = self.random_delay(GUESS_DELAY);
+        let turn = self.turn;
+        ctx.run_later(delay, move |act, ctx| {
+            if act.turn == turn {
+                if let Some(word) = act.random_word() {
+                    act.send(format!("m{}", word));
+                }
+                act.guess(ctx);
+            }
+        });
+    }
+
+    /// Writes a random word as the description in a telephone game
+    fn write(&mut self, ctx: &mut Context<Self>) {
+        self.with_words(ctx, |act, ctx| {
+            if let Some(word) = act.random_word() {
+                let delay = act.random_delay(THINK_DELAY);
+                act.send_later(delay, format!("u{}", word), ctx);
+            }
+        });
+    }
+
+    /// Draws the word from a recording of it. Drawings on the bot's own canvas are
+    /// handed in once they're finished, the bot gives up when it's the only one
+    /// drawing on the shared canvas and has never seen the word drawn.
+    fn draw(&mut self, word: String, alone: bool, own_canvas: bool, ctx: &mut Context<Self>) {
+        let turn = self.turn;
+        self.game_server
+            .send(RecordingRequest { word })
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                if act.turn == turn {
+                    match res {
+                        Ok(records) if !records.is_empty() => {
+                            let record = Arc::clone(records.choose(&mut act.rng).unwrap());
+                            act.replay(record, own_canvas, ctx);
+                        }
+                        _ if own_canvas => {
+                            let delay = act.random_delay(THINK_DELAY);
+                            act.send_later(delay, "u".to_string(), ctx);
+                        }
+                        _ if alone => {
+                            let (id, room) = (act.id, act.room.clone());
+                            let game_server = act.game_server.clone();
+                            ctx.run_later(GIVE_UP_DELAY, move |act, _| {
+                                if act.turn == turn {
+                                    game_server.do_send(GiveUp {
+                                        session_id: id,
+                                        room,
+                                    });
+                                }
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+                fut::ready(())
+            })
+            .spawn(ctx);
+    }
+
+    /// Sends every segment of the recording at the same time it was first drawn,
+    /// scaled to the room's canvas
+    fn replay(&mut self, record: Arc<RoundRecord>, submit: bool, ctx: &mut Context<Self>) {
+        let scale = |value: u16, from: u16, to: u16| {
+            (u32::from(value) * u32::from(to) / u32::from(from.max(1))).min(u32::from(to))
+        };
+        let (from, to) = (record.canvas, self.canvas);
+        let mut commands = VecDeque::new();
+        let mut last = 0;
+        // Strokes are replayed one after the other, even if several people drew
+        // them at once, so that each one is continued before the next starts
+        for (stroke_id, stroke) in record.strokes.iter().enumerate() {
+            for (&(x1, y1, x2, y2, pen_size), &time) in stroke.segments.iter().zip(&stroke.times) {
+                let (x1, x2) = (
+                    scale(x1, from.width, to.width),
+                    scale(x2, from.width, to.width),
+                );
+                let (y1, y2) = (
+                    scale(y1, from.height, to.height),
+                    scale(y2, from.height, to.height),
+                );
+                let pen_size = pen_size.clamp(1, to.max_pen_size);
+                let content = match stroke.tool {
+                    Tool::Pen(colour) => format!(
+                        "d{},{},{},{},{},{},{}",
+                        x1, y1, x2, y2, pen_size, stroke_id, colour
+                    ),
+                    Tool::Eraser => {
+                        format!("x{},{},{},{},{},{}", x1, y1, x2, y2, pen_size, stroke_id)
+                    }
+                    Tool::Fill(colour) => format!("p{},{},{},{}", x1, y1, stroke_id, colour),
+                };
+                last = last.max(time);
+                commands.push_back((Duration::from_millis(last.into()), content));
+            }
+        }
+        if submit {
+            commands.push_back((
+                Duration::from_millis(last.into()) + Duration::from_secs(1),
+                "u".to_string(),
+            ));
+        }
+        self.play(commands, Instant::now(), ctx);
+    }
+
+    /// Sends each command once it's been long enough since `started`, waiting for
+    /// each one to be sent before the next so that they stay in order
+    fn play(
+        &mut self,
+        mut commands: VecDeque<(Duration, String)>,
+        started: Instant,
+        ctx: &mut Context<Self>,
+    ) {
+        if let Some((time, content)) = commands.pop_front() {
+            let turn = self.turn;
+            let delay = time.checked_sub(started.elapsed()).unwrap_or_default();
+            ctx.run_later(delay, move |act, ctx| {
+                if act.turn == turn {
+                    act.send(content);
+                    act.play(commands, started, ctx);
+                }
+            });
+        }
+    }
+}
+
+impl Actor for Bot {
+    type Context = Context<Self>;
+}
+
+impl Handler<Event> for Bot {
+    type Result = ();
+
+    fn handle(&mut self, event: Event, ctx: &mut Context<Self>) {
+        if self.muted && !matches!(event, Event::LeaveRoom) {
+            return;
+        }
+        match event {
+            Event::CanvasSettings(canvas) => self.canvas = canvas,
+            Event::EnterLobby(_) => {
+                self.turn += 1;
+                // The host might pick different word packs for the next game
+                self.words.clear();
+            }
+            Event::NewRound(leaders, _) => {
+                self.turn += 1;
+                if !leaders.contains(&self.id) {
+                    self.with_words(ctx, |act, ctx| act.guess(ctx));
+                }
+            }
+            Event::NewLeader(_, word, others, _) => {
+                self.turn += 1;
+                self.draw(word, others.is_empty(), false, ctx);
+            }
+            Event::SoloStep(drawing, prompt, _) => {
+                self.turn += 1;
+                match (drawing, prompt) {
+                    (true, Some(Prompt::Text(word))) => self.draw(word, false, true, ctx),
+                    (true, _) => {
+                        let delay = self.random_delay(THINK_DELAY);
+                        self.send_later(delay, "u".to_string(), ctx);
+                    }
+                    (false, _) => self.write(ctx),
+                }
+            }
+            // Bots are never picked to describe the word, and can't draw one they
+            // don't know so they hand in a blank canvas
+            Event::ReverseStep(..) => {
+                self.turn += 1;
+                let delay = self.random_delay(THINK_DELAY);
+                self.send_later(delay, "u".to_string(), ctx);
+            }
+            Event::Voting(_, drawings, judge, _) => {
+                self.turn += 1;
+                if judge.is_none() || judge == Some(self.id) {
+                    let id = self.id;
+                    let authors: Vec<_> = drawings
+                        .iter()
+                        .map(|(author, _)| *author)
+                        .filter(|author| *author != id)
+                        .collect();
+                    if let Some(author) = authors.choose(&mut self.rng) {
+                        let content = format!("v{}", author);
+                        let delay = self.random_delay(THINK_DELAY);
+                        self.send_later(delay, content, ctx);
+                    }
+                }
+            }
+            Event::Winner(..) | Event::VoteResults(..) | Event::Gallery(_) => self.turn += 1,
+            Event::LeaveRoom => {
+                trace!("Bot {} left room {}", self.id, self.room);
+                ctx.stop();
+            }
+            _ => {}
+        }
+    }
+}
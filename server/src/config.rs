@@ -0,0 +1,646 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+
+use crate::draw_history::CanvasSettings;
+use crate::game_mode::GAME_MODES;
+use crate::listen::Address;
+use crate::room_key::{self, RoomKeys};
+
+/// The prefix of environment variables that override the config file, followed by
+/// the section and key, e.g. `DRAW_GAME_NETWORK_PORT`
+const ENV_PREFIX: &str = "DRAW_GAME_";
+/// The environment variable with the path to the config file, if it isn't given on
+/// the command line
+const CONFIG_ENV: &str = "DRAW_GAME_CONFIG";
+/// The shortest admin token allowed, so that it can't be guessed
+const MIN_ADMIN_TOKEN_LENGTH: usize = 16;
+/// Settings that are whole numbers but have no default, since the type of every
+/// other setting is taken from its default
+const OPTIONAL_INTEGERS: &[(&str, &str)] = &[("game", "seed")];
+
+/// Everything the server can be configured with. It's read from a TOML file, then
+/// environment variables override the file and command line flags override both.
+/// Anything left out has its default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub network: NetworkConfig,
+    pub logging: LoggingConfig,
+    pub game: GameConfig,
+    pub room_keys: RoomKeyConfig,
+    pub limits: LimitsConfig,
+    pub timers: TimersConfig,
+    pub room: RoomConfig,
+    pub admin: AdminConfig,
+    pub shutdown: ShutdownConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// Where to listen, e.g. `0.0.0.0:3007`, `[::]:3007`, `unix:/run/draw_game.sock`
+    /// or `systemd` for every socket systemd passed in. Only `address` and `port`
+    /// are listened on if there aren't any.
+    pub listen: Vec<String>,
+    pub address: String,
+    pub port: u16,
+    /// Mounted at the root of the server, serving `index.html` at `/`
+    pub serve_dir: Option<PathBuf>,
+    /// The PEM certificate chain to serve HTTPS with, along with `tls_key`. The
+    /// server only serves plain HTTP without them.
+    pub tls_cert: Option<PathBuf>,
+    /// The PEM private key for `tls_cert`
+    pub tls_key: Option<PathBuf>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            listen: Vec::new(),
+            address: "0.0.0.0".to_string(),
+            port: 3007,
+            serve_dir: None,
+            tls_cert: None,
+            tls_key: None,
+        }
+    }
+}
+
+impl NetworkConfig {
+    /// Every address to listen on, which are checked to be valid when the config
+    /// is loaded
+    pub fn listen_addresses(&self) -> Vec<Address> {
+        if self.listen.is_empty() {
+            vec![Address::from_host(&self.address, self.port)]
+        } else {
+            self.listen
+                .iter()
+                .filter_map(|address| address.parse().ok())
+                .collect()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Where log files are written, logs only go to stderr without one
+    pub directory: Option<PathBuf>,
+    /// The log filter, e.g. `server=info`. `RUST_LOG` overrides it.
+    pub level: Option<String>,
+    /// Where game events are appended as JSON lines
+    pub event_log: Option<PathBuf>,
+    /// Where everything sent into the server is recorded, so that it can be replayed
+    pub record: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub word_pack_dir: PathBuf,
+    /// A file of words to censor in chat, one per line, which room keys are also
+    /// checked against
+    pub word_filter: Option<PathBuf>,
+    /// The seed for the game server's RNG, random if there isn't one
+    pub seed: Option<u64>,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            word_pack_dir: PathBuf::from("wordpacks"),
+            word_filter: None,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoomKeyConfig {
+    pub alphabet: String,
+    pub length: usize,
+    pub case_sensitive: bool,
+}
+
+impl Default for RoomKeyConfig {
+    fn default() -> Self {
+        RoomKeyConfig {
+            alphabet: room_key::DEFAULT_ALPHABET.to_string(),
+            length: room_key::DEFAULT_LENGTH,
+            case_sensitive: false,
+        }
+    }
+}
+
+impl RoomKeyConfig {
+    pub fn room_keys(&self) -> Result<RoomKeys, String> {
+        RoomKeys::new(&self.alphabet, self.length, self.case_sensitive)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// The most bots a room can have
+    pub max_bots: usize,
+    /// The longest prompt or description that can be written in telephone games
+    pub max_prompt_length: usize,
+    /// The highest a room's `simplify_draw_history` can be set to
+    pub simplify_draw_history: usize,
+    /// The highest a room's `max_draw_history` can be set to
+    pub max_draw_history: usize,
+    /// How many drawings of each word are kept for bots to draw from
+    pub recordings_per_word: usize,
+    /// How many different words can have drawings kept for bots
+    pub max_recorded_words: usize,
+    /// The most finished rounds a room keeps for its gallery, the game ends once
+    /// there are this many
+    pub max_gallery_rounds: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        LimitsConfig {
+            max_bots: 8,
+            max_prompt_length: 100,
+            simplify_draw_history: 5_000,
+            max_draw_history: 50_000,
+            recordings_per_word: 3,
+            max_recorded_words: 10_000,
+            max_gallery_rounds: 100,
+        }
+    }
+}
+
+/// How long things take, in seconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimersConfig {
+    /// How long players get to draw when the round timer is on
+    pub drawing_limit: u64,
+    /// How long players get to write in telephone games when the round timer is on
+    pub telephone_write_limit: u64,
+    /// How long everyone gets to vote for their favourite drawing when the round
+    /// timer is on
+    pub voting_limit: u64,
+    /// How long the word is shown for before the next round starts
+    pub intermission: u64,
+}
+
+impl Default for TimersConfig {
+    fn default() -> Self {
+        TimersConfig {
+            drawing_limit: 120,
+            telephone_write_limit: 60,
+            voting_limit: 30,
+            intermission: 5,
+        }
+    }
+}
+
+impl TimersConfig {
+    pub fn drawing_limit(&self) -> Duration {
+        Duration::from_secs(self.drawing_limit)
+    }
+
+    pub fn telephone_write_limit(&self) -> Duration {
+        Duration::from_secs(self.telephone_write_limit)
+    }
+
+    pub fn voting_limit(&self) -> Duration {
+        Duration::from_secs(self.voting_limit)
+    }
+
+    pub fn intermission(&self) -> Duration {
+        Duration::from_secs(self.intermission)
+    }
+}
+
+/// The settings rooms start with, which are also used for anything the host leaves
+/// out when starting a game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoomConfig {
+    pub round_timer: bool,
+    pub allow_clear: bool,
+    /// How many rounds are played before the game ends, 0 means there's no limit
+    pub round_limit: usize,
+    pub width: u16,
+    pub height: u16,
+    pub max_pen_size: u16,
+    /// The id of the game mode
+    pub mode: usize,
+    /// Once a round's draw history has this many segments, finished strokes are
+    /// simplified
+    pub simplify_draw_history: usize,
+    /// The most segments a round's draw history can hold, any more draw commands
+    /// are dropped
+    pub max_draw_history: usize,
+}
+
+impl Default for RoomConfig {
+    fn default() -> Self {
+        let canvas = CanvasSettings::default();
+        RoomConfig {
+            round_timer: false,
+            allow_clear: false,
+            round_limit: 0,
+            width: canvas.width,
+            height: canvas.height,
+            max_pen_size: canvas.max_pen_size,
+            mode: 0,
+            simplify_draw_history: 5_000,
+            max_draw_history: 50_000,
+        }
+    }
+}
+
+impl RoomConfig {
+    pub fn round_limit(&self) -> Option<usize> {
+        Some(self.round_limit).filter(|x| *x > 0)
+    }
+
+    /// The canvas settings, which are checked to be valid when the config is loaded
+    pub fn canvas(&self) -> CanvasSettings {
+        CanvasSettings::new(self.width, self.height, self.max_pen_size).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// The bearer token needed to see admin pages such as `/metrics`. They're open
+    /// to anyone without one. It's left out of recordings.
+    #[serde(skip_serializing)]
+    pub token: Option<String>,
+}
+
+/// What happens when the server is sent SIGTERM
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    /// How long players are warned that the server is restarting before it stops,
+    /// in seconds
+    pub countdown: u64,
+    /// Where every room is written as JSON just before the server stops, if
+    /// anywhere
+    pub snapshot: Option<PathBuf>,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig {
+            countdown: 30,
+            snapshot: None,
+        }
+    }
+}
+
+impl ShutdownConfig {
+    pub fn countdown(&self) -> Duration {
+        Duration::from_secs(self.countdown)
+    }
+}
+
+impl Config {
+    /// Loads the config file given on the command line or in `DRAW_GAME_CONFIG`,
+    /// applies the environment variables and flags on top of it and checks the
+    /// result. Every problem found is returned, not just the first.
+    pub fn load(
+        matches: &ArgMatches,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Config, Vec<String>> {
+        let vars: Vec<_> = vars.into_iter().collect();
+        let path = matches.value_of("config").map(PathBuf::from).or_else(|| {
+            vars.iter()
+                .find(|(name, _)| name == CONFIG_ENV)
+                .map(|(_, path)| PathBuf::from(path))
+        });
+        let mut table = match path {
+            Some(path) => read_file(&path).map_err(|e| vec![e])?,
+            None => toml::value::Table::new(),
+        };
+        apply_env(&mut table, vars).map_err(|e| vec![e])?;
+        let mut config: Config = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| vec![format!("Invalid config: {}", e)])?;
+        config.apply_args(matches).map_err(|e| vec![e])?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_args(&mut self, matches: &ArgMatches) -> Result<(), String> {
+        fn parse<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+            value
+                .parse()
+                .map_err(|_| format!("Invalid value {:?} for {}", value, name))
+        }
+
+        if let Some(dir) = matches.value_of("serve_dir") {
+            self.network.serve_dir = Some(dir.into());
+        }
+        if let Some(path) = matches.value_of("tls_cert") {
+            self.network.tls_cert = Some(path.into());
+        }
+        if let Some(path) = matches.value_of("tls_key") {
+            self.network.tls_key = Some(path.into());
+        }
+        if let Some(addresses) = matches.values_of("listen") {
+            self.network.listen = addresses.map(|address| address.to_string()).collect();
+        }
+        if let Some(port) = matches.value_of("port") {
+            self.network.port = parse(port, "the port")?;
+        }
+        if let Some(dir) = matches.value_of("word_pack_dir") {
+            self.game.word_pack_dir = dir.into();
+        }
+        if let Some(path) = matches.value_of("word_filter") {
+            self.game.word_filter = Some(path.into());
+        }
+        if let Some(seed) = matches.value_of("seed") {
+            self.game.seed = Some(parse(seed, "the seed")?);
+        }
+        if let Some(dir) = matches.value_of("log") {
+            self.logging.directory = Some(dir.into());
+        }
+        if let Some(path) = matches.value_of("event_log") {
+            self.logging.event_log = Some(path.into());
+        }
+        if let Some(path) = matches.value_of("record") {
+            self.logging.record = Some(path.into());
+        }
+        if let Some(alphabet) = matches.value_of("key_alphabet") {
+            self.room_keys.alphabet = alphabet.to_string();
+        }
+        if let Some(length) = matches.value_of("key_length") {
+            self.room_keys.length = parse(length, "the key length")?;
+        }
+        if matches.is_present("key_case_sensitive") {
+            self.room_keys.case_sensitive = true;
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if let Err(e) = self.room_keys.room_keys() {
+            errors.push(format!("Invalid room key format: {}", e));
+        }
+        if self.limits.max_prompt_length == 0 {
+            errors.push("limits.max_prompt_length must be more than 0".to_string());
+        }
+        if self.room.max_draw_history == 0 {
+            errors.push("room.max_draw_history must be more than 0".to_string());
+        }
+        if self.room.simplify_draw_history > self.room.max_draw_history {
+            errors.push(
+                "room.simplify_draw_history can't be more than room.max_draw_history".to_string(),
+            );
+        }
+        for (name, room, limit) in [
+            (
+                "max_draw_history",
+                self.room.max_draw_history,
+                self.limits.max_draw_history,
+            ),
+            (
+                "simplify_draw_history",
+                self.room.simplify_draw_history,
+                self.limits.simplify_draw_history,
+            ),
+        ]
+        .iter()
+        {
+            if room > limit {
+                errors.push(format!("room.{0} can't be more than limits.{0}", name));
+            }
+        }
+        if self.limits.recordings_per_word == 0 {
+            errors.push("limits.recordings_per_word must be more than 0".to_string());
+        }
+        if self.limits.max_gallery_rounds == 0 {
+            errors.push("limits.max_gallery_rounds must be more than 0".to_string());
+        }
+        let timers = [
+            ("drawing_limit", self.timers.drawing_limit),
+            ("telephone_write_limit", self.timers.telephone_write_limit),
+            ("voting_limit", self.timers.voting_limit),
+        ];
+        for (name, seconds) in timers.iter() {
+            if *seconds == 0 {
+                errors.push(format!("timers.{} must be more than 0 seconds", name));
+            }
+        }
+        if CanvasSettings::new(self.room.width, self.room.height, self.room.max_pen_size).is_none()
+        {
+            errors.push(
+                "The room canvas must be 100 to 2000 pixels wide and high, with a max pen size of 1 to 50"
+                    .to_string(),
+            );
+        }
+        if self.room.mode >= GAME_MODES.len() {
+            errors.push(format!(
+                "room.mode must be the id of a game mode, less than {}",
+                GAME_MODES.len()
+            ));
+        }
+        if let Some(token) = &self.admin.token {
+            if token.chars().count() < MIN_ADMIN_TOKEN_LENGTH {
+                errors.push(format!(
+                    "admin.token must be at least {} characters long",
+                    MIN_ADMIN_TOKEN_LENGTH
+                ));
+            }
+        }
+        if !self.game.word_pack_dir.is_dir() {
+            errors.push(format!(
+                "The word pack directory {:?} doesn't exist",
+                self.game.word_pack_dir
+            ));
+        }
+        for address in &self.network.listen {
+            if let Err(e) = address.parse::<Address>() {
+                errors.push(format!("Invalid listen address: {}", e));
+            }
+        }
+        if self.network.tls_cert.is_some() != self.network.tls_key.is_some() {
+            errors.push("network.tls_cert and network.tls_key must be given together".to_string());
+        }
+        if let Some(dir) = &self.network.serve_dir {
+            if !dir.is_dir() {
+                errors.push(format!("The directory to serve {:?} doesn't exist", dir));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn read_file(path: &Path) -> Result<toml::value::Table, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Couldn't read the config file {:?}: {}", path, e))?;
+    toml::from_str(&contents).map_err(|e| format!("Invalid config file {:?}: {}", path, e))
+}
+
+/// Sets the key in the table for each `DRAW_GAME_<SECTION>_<KEY>` variable. Each
+/// value is read as the type of the setting it's for, with lists separated by
+/// commas, so a string that looks like a number stays a string.
+fn apply_env(
+    table: &mut toml::value::Table,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<(), String> {
+    // `room_keys` comes before `room` so that it's matched first
+    let sections = [
+        "room_keys",
+        "network",
+        "logging",
+        "limits",
+        "timers",
+        "admin",
+        "shutdown",
+        "game",
+        "room",
+    ];
+    let defaults = toml::Value::try_from(Config::default())
+        .expect("The default config couldn't be turned into TOML");
+    for (name, value) in vars {
+        if name == CONFIG_ENV || !name.starts_with(ENV_PREFIX) {
+            continue;
+        }
+        let rest = name[ENV_PREFIX.len()..].to_lowercase();
+        let (section, key) = sections
+            .iter()
+            .find_map(|section| {
+                rest.strip_prefix(section)
+                    .and_then(|key| key.strip_prefix('_'))
+                    .map(|key| (*section, key))
+            })
+            .ok_or_else(|| format!("Unknown config environment variable {}", name))?;
+        let invalid = |kind| format!("{} must be {}, not {:?}", name, kind, value);
+        let integer = || {
+            value
+                .parse()
+                .map(toml::Value::Integer)
+                .map_err(|_| invalid("a whole number"))
+        };
+        let value = match defaults.get(section).and_then(|section| section.get(key)) {
+            Some(toml::Value::Integer(_)) => integer()?,
+            None if OPTIONAL_INTEGERS.contains(&(section, key)) => integer()?,
+            Some(toml::Value::Boolean(_)) => {
+                toml::Value::Boolean(value.parse().map_err(|_| invalid("true or false"))?)
+            }
+            Some(toml::Value::Array(_)) => toml::Value::Array(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| toml::Value::String(item.to_string()))
+                    .collect(),
+            ),
+            // Anything else is a string or a path, or isn't a setting at all, which
+            // is reported when the config is read
+            _ => toml::Value::String(value),
+        };
+        match table
+            .entry(section.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::value::Table::new()))
+        {
+            toml::Value::Table(table) => {
+                table.insert(key.to_string(), value);
+            }
+            _ => return Err(format!("The config's {} isn't a section", section)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::load_yaml;
+
+    fn load(args: &[&str], vars: &[(&str, &str)]) -> Result<Config, Vec<String>> {
+        let yaml = load_yaml!("cli.yml");
+        let matches = clap::App::from(yaml)
+            .get_matches_from(std::iter::once("server").chain(args.iter().copied()));
+        // The word packs have to exist wherever the tests are run from
+        let word_pack_dir = (
+            "DRAW_GAME_GAME_WORD_PACK_DIR",
+            concat!(env!("CARGO_MANIFEST_DIR"), "/wordpacks"),
+        );
+        let vars = std::iter::once(&word_pack_dir)
+            .chain(vars)
+            .map(|(name, value)| (name.to_string(), value.to_string()));
+        Config::load(&matches, vars)
+    }
+
+    #[test]
+    fn env_overrides_the_file_and_flags_override_both() {
+        let path =
+            std::env::temp_dir().join(format!("draw_game_config_{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[network]\nport = 1000\naddress = \"127.0.0.1\"\n[timers]\ndrawing_limit = 10\n",
+        )
+        .unwrap();
+        let path = path.to_str().unwrap();
+        let config = load(
+            &["-p", "3000"],
+            &[
+                (CONFIG_ENV, path),
+                ("DRAW_GAME_NETWORK_PORT", "2000"),
+                ("DRAW_GAME_TIMERS_DRAWING_LIMIT", "20"),
+            ],
+        )
+        .unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(config.network.address, "127.0.0.1");
+        assert_eq!(config.network.port, 3000);
+        assert_eq!(config.timers.drawing_limit, 20);
+    }
+
+    #[test]
+    fn env_values_are_read_as_the_setting_type() {
+        let config = load(
+            &[],
+            &[
+                ("DRAW_GAME_ADMIN_TOKEN", "12345678901234567890"),
+                ("DRAW_GAME_ROOM_KEYS_ALPHABET", "0x123456"),
+                ("DRAW_GAME_ROOM_KEYS_CASE_SENSITIVE", "true"),
+                ("DRAW_GAME_GAME_SEED", "42"),
+                (
+                    "DRAW_GAME_NETWORK_LISTEN",
+                    "unix:/tmp/draw_game.sock, 127.0.0.1:3007",
+                ),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.admin.token.as_deref(), Some("12345678901234567890"));
+        assert_eq!(config.room_keys.alphabet, "0x123456");
+        assert!(config.room_keys.case_sensitive);
+        assert_eq!(config.game.seed, Some(42));
+        assert_eq!(
+            config.network.listen,
+            ["unix:/tmp/draw_game.sock", "127.0.0.1:3007"]
+        );
+    }
+
+    #[test]
+    fn invalid_env_values_are_reported() {
+        let errors = load(&[], &[("DRAW_GAME_NETWORK_PORT", "http")]).unwrap_err();
+        assert_eq!(
+            errors,
+            ["DRAW_GAME_NETWORK_PORT must be a whole number, not \"http\""]
+        );
+        let errors = load(&[], &[("DRAW_GAME_NETWORK_COLOUR", "blue")]).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("colour"), "{:?}", errors);
+    }
+}
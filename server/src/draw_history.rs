@@ -0,0 +1,586 @@
+use std::collections::HashSet;
+
+/// A line segment: (x1, y1, x2, y2, pen_size). Everything fits in a u16 since the
+/// canvas is small, which halves the memory used by long draw histories.
+pub type Segment = (u16, u16, u16, u16, u16);
+
+/// How far in pixels a point can be from the simplified line before it has to be
+/// kept. Small enough that the simplified drawing looks the same.
+const SIMPLIFY_TOLERANCE: f32 = 1.0;
+
+/// The colours that can be drawn with, referred to by index. This must be kept in
+/// sync with the palette in the client.
+pub const PALETTE: [(u8, u8, u8); 12] = [
+    (0x00, 0x00, 0x00),
+    (0xff, 0xff, 0xff),
+    (0x80, 0x80, 0x80),
+    (0xe5, 0x39, 0x35),
+    (0xfb, 0x8c, 0x00),
+    (0xfd, 0xd8, 0x35),
+    (0x43, 0xa0, 0x47),
+    (0x03, 0x9b, 0xe5),
+    (0x1e, 0x35, 0xe5),
+    (0x8e, 0x24, 0xaa),
+    (0xf0, 0x62, 0x92),
+    (0x6d, 0x4c, 0x41),
+];
+
+/// The size of the canvas and the biggest pen that can be drawn with on it
+#[derive(Debug, Clone, Copy)]
+pub struct CanvasSettings {
+    pub width: u16,
+    pub height: u16,
+    pub max_pen_size: u16,
+}
+
+impl CanvasSettings {
+    /// Creates the settings if they are within sensible limits
+    pub fn new(width: u16, height: u16, max_pen_size: u16) -> Option<CanvasSettings> {
+        if (100..=2000).contains(&width)
+            && (100..=2000).contains(&height)
+            && (1..=50).contains(&max_pen_size)
+        {
+            Some(CanvasSettings {
+                width,
+                height,
+                max_pen_size,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Whether the segment fits on the canvas and uses an allowed pen size
+    pub fn contains(&self, (x1, y1, x2, y2, pen_size): Segment) -> bool {
+        x1 <= self.width
+            && y1 <= self.height
+            && x2 <= self.width
+            && y2 <= self.height
+            && pen_size <= self.max_pen_size
+    }
+}
+
+impl Default for CanvasSettings {
+    fn default() -> CanvasSettings {
+        CanvasSettings {
+            width: 500,
+            height: 500,
+            max_pen_size: 10,
+        }
+    }
+}
+
+/// What a stroke was drawn with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+    /// Draws lines in the palette colour with the given index
+    Pen(usize),
+    /// Rubs out lines back to the blank canvas
+    Eraser,
+    /// Flood fills with the palette colour with the given index. Fill strokes have a
+    /// single segment whose start point is where the fill starts.
+    Fill(usize),
+}
+
+/// A continuous line drawn without lifting the pen, or a single fill
+#[derive(Debug, Clone)]
+pub struct Stroke {
+    /// The id the author gave the stroke, which is only unique among their strokes
+    pub id: usize,
+    /// The session id of whoever drew the stroke
+    pub author: usize,
+    pub tool: Tool,
+    pub segments: Vec<Segment>,
+    /// When each segment was drawn, in milliseconds since the start of the round
+    pub times: Vec<u32>,
+}
+
+/// Everything drawn on a canvas this round, grouped into strokes so that they can
+/// be undone and redone. Several people can draw on the same canvas at once, so each
+/// author continues, undoes and redoes only their own strokes.
+#[derive(Debug, Default)]
+pub struct DrawHistory {
+    strokes: Vec<Stroke>,
+    undone: Vec<Stroke>,
+    /// The total number of segments in `strokes`
+    segment_count: usize,
+    /// Strokes before this index have already been simplified
+    simplified: usize,
+}
+
+impl DrawHistory {
+    /// Adds a segment to the author's stroke with the given id. If that isn't the
+    /// author's most recent stroke, or it has already been simplified, a new one is
+    /// started, which also means the author can't redo anything anymore. The new
+    /// stroke is given an id the author hasn't used if the one asked for is taken.
+    ///
+    /// Returns the id of the stroke the segment was added to, or `None` if it can't
+    /// be added because it was drawn with a different tool or the stroke is a fill.
+    pub fn push_segment(
+        &mut self,
+        author: usize,
+        stroke_id: usize,
+        tool: Tool,
+        segment: Segment,
+        time: u32,
+    ) -> Option<usize> {
+        let simplified = self.simplified;
+        match self.latest_stroke(author) {
+            Some(index) if index >= simplified && self.strokes[index].id == stroke_id => {
+                let stroke = &mut self.strokes[index];
+                if stroke.tool != tool || matches!(tool, Tool::Fill(_)) {
+                    return None;
+                }
+                stroke.segments.push(segment);
+                stroke.times.push(time);
+                self.segment_count += 1;
+                Some(stroke_id)
+            }
+            _ => {
+                self.undone.retain(|stroke| stroke.author != author);
+                let taken = self
+                    .strokes
+                    .iter()
+                    .any(|stroke| stroke.author == author && stroke.id == stroke_id);
+                let id = if taken {
+                    self.unused_stroke_id(author)
+                } else {
+                    stroke_id
+                };
+                self.strokes.push(Stroke {
+                    id,
+                    author,
+                    tool,
+                    segments: vec![segment],
+                    times: vec![time],
+                });
+                self.segment_count += 1;
+                Some(id)
+            }
+        }
+    }
+
+    /// The index of the author's most recent stroke
+    fn latest_stroke(&self, author: usize) -> Option<usize> {
+        self.strokes
+            .iter()
+            .rposition(|stroke| stroke.author == author)
+    }
+
+    /// One more than the biggest id the author has used, or 0 if they haven't
+    /// drawn anything
+    fn unused_stroke_id(&self, author: usize) -> usize {
+        self.strokes
+            .iter()
+            .chain(&self.undone)
+            .filter(|stroke| stroke.author == author)
+            .map(|stroke| stroke.id + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The stroke id for draw commands that don't say which stroke they belong to.
+    /// They continue the author's most recent stroke if it was drawn with the same
+    /// tool and hasn't been simplified, otherwise they start a new stroke with an id
+    /// the author hasn't used.
+    pub fn continuing_stroke_id(&self, author: usize, tool: Tool) -> usize {
+        match self.latest_stroke(author) {
+            Some(index)
+                if index >= self.simplified
+                    && self.strokes[index].tool == tool
+                    && !matches!(tool, Tool::Fill(_)) =>
+            {
+                self.strokes[index].id
+            }
+            _ => self.unused_stroke_id(author),
+        }
+    }
+
+    /// Removes the author's most recent stroke, returning its id
+    pub fn undo(&mut self, author: usize) -> Option<usize> {
+        let index = self.latest_stroke(author)?;
+        let stroke = self.strokes.remove(index);
+        let id = stroke.id;
+        self.segment_count -= stroke.segments.len();
+        if index < self.simplified {
+            self.simplified -= 1;
+        }
+        self.undone.push(stroke);
+        Some(id)
+    }
+
+    /// Restores the author's most recently undone stroke
+    pub fn redo(&mut self, author: usize) -> Option<&Stroke> {
+        let index = self
+            .undone
+            .iter()
+            .rposition(|stroke| stroke.author == author)?;
+        let stroke = self.undone.remove(index);
+        self.segment_count += stroke.segments.len();
+        self.strokes.push(stroke);
+        self.strokes.last()
+    }
+
+    pub fn clear(&mut self) {
+        self.strokes.clear();
+        self.undone.clear();
+        self.segment_count = 0;
+        self.simplified = 0;
+    }
+
+    /// The strokes that are currently visible on the canvas
+    pub fn strokes(&self) -> &[Stroke] {
+        &self.strokes
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segment_count
+    }
+
+    /// Simplifies every finished stroke that hasn't been simplified yet. The most
+    /// recent stroke of each author is left alone as it might still be being drawn,
+    /// along with everything after it.
+    pub fn simplify_finished(&mut self) {
+        let mut authors = HashSet::new();
+        let mut finished = self.strokes.len();
+        // Strokes that were already simplified can't be continued anyway
+        for (index, stroke) in self.strokes.iter().enumerate().skip(self.simplified).rev() {
+            if authors.insert(stroke.author) {
+                finished = index;
+            }
+        }
+        self.simplify_until(finished);
+    }
+
+    /// Simplifies every stroke, used once nothing more will be drawn
+    pub fn simplify_all(&mut self) {
+        self.simplify_until(self.strokes.len());
+    }
+
+    fn simplify_until(&mut self, end: usize) {
+        if self.simplified >= end {
+            return;
+        }
+        for stroke in &mut self.strokes[self.simplified..end] {
+            let before = stroke.segments.len();
+            stroke.simplify();
+            self.segment_count -= before - stroke.segments.len();
+        }
+        self.simplified = end;
+    }
+}
+
+impl Stroke {
+    /// Removes points that barely change the shape of the stroke using the
+    /// Ramer-Douglas-Peucker algorithm. The client sends each segment from the new
+    /// pen position back to the previous one, so runs of segments that join up and
+    /// have the same pen size are treated as a single line.
+    fn simplify(&mut self) {
+        if let Tool::Fill(_) = self.tool {
+            return;
+        }
+
+        let mut segments = Vec::with_capacity(self.segments.len());
+        let mut times = Vec::with_capacity(self.times.len());
+        let mut start = 0;
+        while start < self.segments.len() {
+            let pen_size = self.segments[start].4;
+            let mut end = start + 1;
+            while end < self.segments.len() {
+                let (_, _, x2, y2, size) = self.segments[end];
+                let (x1, y1, _, _, _) = self.segments[end - 1];
+                if size != pen_size || (x1, y1) != (x2, y2) {
+                    break;
+                }
+                end += 1;
+            }
+            simplify_run(
+                &self.segments[start..end],
+                &self.times[start..end],
+                &mut segments,
+                &mut times,
+            );
+            start = end;
+        }
+        self.segments = segments;
+        self.times = times;
+    }
+}
+
+/// Simplifies a run of joined up segments with the same pen size, adding the
+/// simplified segments to the output
+fn simplify_run(
+    run: &[Segment],
+    run_times: &[u32],
+    segments: &mut Vec<Segment>,
+    times: &mut Vec<u32>,
+) {
+    let pen_size = run[0].4;
+    // A run starting with a dot has that dot kept so that the start stays rounded
+    let mut first = 0;
+    let (x1, y1, x2, y2, _) = run[0];
+    if (x1, y1) == (x2, y2) {
+        segments.push(run[0]);
+        times.push(run_times[0]);
+        first = 1;
+    }
+    if first >= run.len() {
+        return;
+    }
+
+    // Each point along the line along with when it was drawn
+    let mut points = vec![((run[first].2, run[first].3), run_times[first])];
+    points.extend(
+        run[first..]
+            .iter()
+            .zip(&run_times[first..])
+            .map(|((x1, y1, _, _, _), time)| ((*x1, *y1), *time)),
+    );
+
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    mark_points(&points, 0, points.len() - 1, &mut keep);
+
+    let mut previous = points[0].0;
+    for (((x, y), time), _) in points.iter().zip(&keep).skip(1).filter(|(_, k)| **k) {
+        segments.push((*x, *y, previous.0, previous.1, pen_size));
+        times.push(*time);
+        previous = (*x, *y);
+    }
+}
+
+/// Marks the points between start and end that need to be kept. Runs still to be
+/// checked are kept on a stack rather than recursed into, since a long stroke can
+/// be split up one point at a time.
+fn mark_points(points: &[((u16, u16), u32)], start: usize, end: usize, keep: &mut [bool]) {
+    let mut runs = vec![(start, end)];
+    while let Some((start, end)) = runs.pop() {
+        if end <= start + 1 {
+            continue;
+        }
+        let ((sx, sy), _) = points[start];
+        let ((ex, ey), _) = points[end];
+        let (sx, sy, ex, ey) = (sx as f32, sy as f32, ex as f32, ey as f32);
+        let (dx, dy) = (ex - sx, ey - sy);
+        let length = (dx * dx + dy * dy).sqrt();
+
+        let (furthest, distance) = points[start + 1..end]
+            .iter()
+            .enumerate()
+            .map(|(i, ((x, y), _))| {
+                let (px, py) = (*x as f32 - sx, *y as f32 - sy);
+                let distance = if length == 0.0 {
+                    (px * px + py * py).sqrt()
+                } else {
+                    (px * dy - py * dx).abs() / length
+                };
+                (start + 1 + i, distance)
+            })
+            .fold(
+                (start, 0.0),
+                |best, point| {
+                    if point.1 > best.1 {
+                        point
+                    } else {
+                        best
+                    }
+                },
+            );
+
+        if distance > SIMPLIFY_TOLERANCE {
+            keep[furthest] = true;
+            runs.push((start, furthest));
+            runs.push((furthest, end));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEN: Tool = Tool::Pen(0);
+
+    /// The segments the client sends for a line through the points, each one from
+    /// the new pen position back to the previous one
+    fn line(points: &[(u16, u16)]) -> Vec<Segment> {
+        points
+            .windows(2)
+            .map(|pair| (pair[1].0, pair[1].1, pair[0].0, pair[0].1, 2))
+            .collect()
+    }
+
+    fn draw(history: &mut DrawHistory, author: usize, stroke_id: usize, segments: &[Segment]) {
+        for segment in segments {
+            assert_eq!(
+                history.push_segment(author, stroke_id, PEN, *segment, 0),
+                Some(stroke_id)
+            );
+        }
+    }
+
+    fn ids(history: &DrawHistory) -> Vec<(usize, usize)> {
+        history
+            .strokes()
+            .iter()
+            .map(|stroke| (stroke.author, stroke.id))
+            .collect()
+    }
+
+    /// How far the point is from the closest of the segments
+    fn distance_to(segments: &[Segment], (x, y): (u16, u16)) -> f32 {
+        let (px, py) = (x as f32, y as f32);
+        segments
+            .iter()
+            .map(|(x1, y1, x2, y2, _)| {
+                let (x1, y1, x2, y2) = (*x1 as f32, *y1 as f32, *x2 as f32, *y2 as f32);
+                let (dx, dy) = (x2 - x1, y2 - y1);
+                let length = dx * dx + dy * dy;
+                let t = if length == 0.0 {
+                    0.0
+                } else {
+                    (((px - x1) * dx + (py - y1) * dy) / length).clamp(0.0, 1.0)
+                };
+                let (cx, cy) = (x1 + t * dx - px, y1 + t * dy - py);
+                (cx * cx + cy * cy).sqrt()
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn segments_are_grouped_into_strokes_by_id() {
+        let mut history = DrawHistory::default();
+        draw(&mut history, 1, 0, &line(&[(0, 0), (5, 5), (10, 5)]));
+        draw(&mut history, 2, 0, &line(&[(0, 9), (9, 9)]));
+        draw(&mut history, 1, 1, &line(&[(20, 20), (30, 30)]));
+        assert_eq!(ids(&history), [(1, 0), (2, 0), (1, 1)]);
+        assert_eq!(history.segment_count(), 4);
+
+        // A stroke can't change tool part way through
+        let eraser = history.push_segment(1, 1, Tool::Eraser, (1, 1, 2, 2, 2), 0);
+        assert_eq!(eraser, None);
+        assert_eq!(history.segment_count(), 4);
+    }
+
+    #[test]
+    fn undo_and_redo_only_change_the_authors_strokes() {
+        let mut history = DrawHistory::default();
+        draw(&mut history, 1, 0, &line(&[(0, 0), (5, 5)]));
+        draw(&mut history, 2, 0, &line(&[(0, 9), (9, 9)]));
+        draw(&mut history, 1, 1, &line(&[(20, 20), (30, 30)]));
+
+        assert_eq!(history.undo(1), Some(1));
+        assert_eq!(history.undo(1), Some(0));
+        assert_eq!(history.undo(1), None);
+        assert_eq!(ids(&history), [(2, 0)]);
+
+        assert_eq!(history.redo(1).map(|stroke| stroke.id), Some(0));
+        assert_eq!(ids(&history), [(2, 0), (1, 0)]);
+        assert_eq!(history.segment_count(), 2);
+
+        // Drawing something new means the undone stroke can't come back
+        draw(&mut history, 1, 2, &line(&[(40, 40), (50, 50)]));
+        assert!(history.redo(1).is_none());
+        assert_eq!(ids(&history), [(2, 0), (1, 0), (1, 2)]);
+    }
+
+    #[test]
+    fn undo_and_redo_work_across_simplification() {
+        let mut history = DrawHistory::default();
+        let straight: Vec<_> = (0..20).map(|x| (x * 5, 50)).collect();
+        draw(&mut history, 1, 0, &line(&straight));
+        draw(&mut history, 1, 1, &line(&[(0, 0), (5, 5)]));
+        history.simplify_finished();
+        // Only the stroke that might still be being drawn is left as it is
+        let lengths: Vec<_> = history.strokes().iter().map(|s| s.segments.len()).collect();
+        assert_eq!(lengths, [1, 1]);
+        assert_eq!(history.segment_count(), 2);
+
+        assert_eq!(history.undo(1), Some(1));
+        assert_eq!(history.undo(1), Some(0));
+        assert_eq!(history.segment_count(), 0);
+        assert_eq!(history.redo(1).map(|stroke| stroke.id), Some(0));
+        assert_eq!(history.redo(1).map(|stroke| stroke.id), Some(1));
+        assert_eq!(ids(&history), [(1, 0), (1, 1)]);
+        assert_eq!(history.segment_count(), 2);
+    }
+
+    #[test]
+    fn simplified_strokes_are_never_continued() {
+        let mut history = DrawHistory::default();
+        draw(&mut history, 1, 0, &line(&[(0, 0), (5, 5), (10, 10)]));
+        draw(&mut history, 1, 1, &line(&[(20, 20), (30, 30)]));
+        history.simplify_finished();
+        assert_eq!(history.undo(1), Some(1));
+
+        // The simplified stroke is now the author's latest, so drawing with its id
+        // starts a new stroke which needs an id of its own
+        assert_eq!(history.continuing_stroke_id(1, PEN), 2);
+        assert_eq!(history.push_segment(1, 0, PEN, (1, 1, 0, 0, 2), 0), Some(1));
+        assert_eq!(ids(&history), [(1, 0), (1, 1)]);
+
+        // Undoing takes away the new stroke and then the simplified one
+        assert_eq!(history.undo(1), Some(1));
+        assert_eq!(ids(&history), [(1, 0)]);
+        assert_eq!(history.undo(1), Some(0));
+        assert!(history.strokes().is_empty());
+        assert_eq!(history.segment_count(), 0);
+    }
+
+    #[test]
+    fn simplifying_keeps_every_point_within_the_tolerance() {
+        // A wobbly line with a bump in the middle that has to be kept
+        let points: Vec<(u16, u16)> = (0..200)
+            .map(|x| {
+                let wobble = (x % 3) / 2;
+                let bump = if (90..110).contains(&x) { 20 } else { 0 };
+                (x, 100 + wobble + bump)
+            })
+            .collect();
+        let mut stroke = Stroke {
+            id: 0,
+            author: 1,
+            tool: PEN,
+            segments: line(&points),
+            times: (0..points.len() as u32 - 1).collect(),
+        };
+        stroke.simplify();
+
+        assert!(stroke.segments.len() < points.len() / 10);
+        assert_eq!(stroke.segments.len(), stroke.times.len());
+        assert_eq!(
+            stroke.segments.first().map(|s| (s.2, s.3)),
+            points.first().copied()
+        );
+        assert_eq!(
+            stroke.segments.last().map(|s| (s.0, s.1)),
+            points.last().copied()
+        );
+        for point in points {
+            let distance = distance_to(&stroke.segments, point);
+            assert!(
+                distance <= SIMPLIFY_TOLERANCE,
+                "{:?} is {} from the simplified line",
+                point,
+                distance
+            );
+        }
+    }
+
+    #[test]
+    fn simplifying_a_long_stroke_keeps_every_corner() {
+        // A zigzag where every point is a corner, so none of them can be dropped
+        let points: Vec<(u16, u16)> = (0..20_000).map(|x| (x, 100 + x % 2 * 10)).collect();
+        let mut stroke = Stroke {
+            id: 0,
+            author: 1,
+            tool: PEN,
+            segments: line(&points),
+            times: (0..points.len() as u32 - 1).collect(),
+        };
+        stroke.simplify();
+
+        assert_eq!(stroke.segments, line(&points));
+    }
+}
@@ -0,0 +1,119 @@
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::time::SystemTime;
+
+use serde::Serialize;
+
+use crate::json_lines::JsonLines;
+
+/// Something that happened in a game, written to the event log
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum GameEvent<'a> {
+    RoomCreated {
+        session: usize,
+        username: &'a str,
+    },
+    Joined {
+        session: usize,
+        username: &'a str,
+    },
+    Left {
+        session: usize,
+    },
+    /// The game settings the host started a game with
+    Settings {
+        session: usize,
+        mode: &'a str,
+        mode_options: &'a str,
+        word_packs: &'a [usize],
+        custom_words: usize,
+        round_timer: bool,
+        allow_clear: bool,
+        round_limit: Option<usize>,
+        width: u16,
+        height: u16,
+        max_pen_size: u16,
+        max_draw_history: usize,
+        simplify_draw_history: usize,
+        players: &'a [usize],
+    },
+    /// A round has started, with everyone drawing it or the one describing it in
+    /// reverse rounds. Everyone draws in rounds without any leaders.
+    RoundStart {
+        round: usize,
+        leaders: &'a [usize],
+        word: &'a str,
+    },
+    Guess {
+        session: usize,
+        guess: &'a str,
+        correct: bool,
+    },
+    Win {
+        round: usize,
+        winner: usize,
+        leaders: &'a [usize],
+        word: &'a str,
+        points: usize,
+    },
+    /// A step ran out of time, with the state the room was in. The word is null
+    /// in games where everyone has their own.
+    Timeout {
+        round: usize,
+        state: &'a str,
+        leaders: &'a [usize],
+        word: Option<&'a str>,
+    },
+    /// A player finished their part of a step in a game where everyone plays at
+    /// once, such as handing in a drawing or voting
+    Submitted {
+        session: usize,
+        state: &'a str,
+    },
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    /// Milliseconds since the unix epoch
+    time: u128,
+    room: &'a str,
+    #[serde(flatten)]
+    event: GameEvent<'a>,
+}
+
+/// Writes game events as JSON, one per line, so that games can be analysed
+/// offline. Does nothing unless the server was given a file to log to.
+#[derive(Default)]
+pub struct EventLog {
+    file: Option<JsonLines>,
+}
+
+impl EventLog {
+    /// Appends to the file, creating it if it doesn't exist
+    pub fn new<P: AsRef<Path>>(path: P) -> std::io::Result<EventLog> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(EventLog {
+            file: Some(JsonLines::new("event log", file)),
+        })
+    }
+
+    pub fn record(&self, room: &str, event: GameEvent) {
+        let file = match &self.file {
+            Some(file) => file,
+            None => return,
+        };
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("went backwards in time")
+            .as_millis();
+        file.write(&Record { time, room, event });
+    }
+
+    /// Waits for every event recorded so far to be written
+    pub fn close(&self) {
+        if let Some(file) = &self.file {
+            file.close();
+        }
+    }
+}
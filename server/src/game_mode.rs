@@ -0,0 +1,277 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use log::warn;
+
+use crate::{
+    config::TimersConfig,
+    draw_history::Stroke,
+    metrics::StateName,
+    room::{Occupant, RoomHandle},
+    word_pack::{WordChooser, WordPack},
+};
+
+mod collaborative;
+mod draw_off;
+mod reverse;
+mod teams;
+mod telephone;
+mod voting;
+
+pub use collaborative::Collaborative;
+pub use draw_off::DrawOff;
+pub use reverse::Reverse;
+pub use teams::Teams;
+pub use telephone::{Entry, Telephone};
+
+/// What a chat message sent during a round means for the game
+pub enum Guess {
+    /// The message guessed the word, with the alternate it matched if it wasn't the
+    /// main word
+    Correct(Option<usize>),
+    /// The message is shown to everyone but doesn't guess the word
+    Chat,
+    /// The message isn't shown to anyone, such as the leader giving away the word
+    Blocked,
+}
+
+/// What a player is shown to draw or describe when everyone works on their own
+#[derive(Clone, Debug)]
+pub enum Prompt {
+    Text(String),
+    Drawing(Vec<Stroke>),
+}
+
+/// A team's points and the session ids of its members
+#[derive(Clone, Debug)]
+pub struct TeamScore {
+    pub points: usize,
+    pub members: Vec<usize>,
+}
+
+/// What a room does once a game where everyone plays at once has moved on
+pub enum Next {
+    /// The game carries on as it is
+    Continue,
+    /// The round is over, and the next one starts after the intermission
+    NextRound,
+    /// The game is over and the room goes back to the lobby
+    EndGame,
+}
+
+/// A game where everyone plays at the same time on their own canvas, instead of
+/// taking turns drawing on one. It runs itself through the room it's given, and
+/// `Room` just passes on everything players do.
+pub trait SoloGame {
+    /// The name of the stage the game is at, used as a label in the metrics
+    fn state_name(&self) -> StateName;
+
+    /// Starts a new round, or the whole game in modes played in one go. Returns
+    /// `Next::EndGame` if there aren't the players for one to start.
+    fn new_round(&mut self, room: &mut RoomHandle) -> Next;
+
+    /// Shows someone who joined part way through what's going on
+    fn joined(&mut self, session_id: usize, room: &mut RoomHandle);
+
+    /// Called when someone leaves part way through, once their canvas has gone.
+    /// Returns true if the current step can't go on without them.
+    fn left(&mut self, _session_id: usize, _room: &mut RoomHandle) -> bool {
+        false
+    }
+
+    /// Handles a player finishing their part of the current step, returning
+    /// whether it was accepted
+    fn submit(&mut self, session_id: usize, content: &str, room: &mut RoomHandle) -> bool;
+
+    /// Handles a vote, returning whether it was accepted. By default there's
+    /// nothing to vote on.
+    fn vote(&mut self, session_id: usize, _data: &str, room: &mut RoomHandle) -> bool {
+        warn!(
+            "vote sent by {} in room {} when there was nothing to vote on",
+            session_id,
+            room.key()
+        );
+        false
+    }
+
+    /// Whether a chat message has to be kept from everyone else
+    fn blocks_message(&self, _session_id: usize, _message: &str, _room: &RoomHandle) -> bool {
+        false
+    }
+
+    /// The leaders and word of the current round if it has them, for the event log
+    fn describe_round(&self, _room: &RoomHandle) -> (Vec<usize>, Option<String>) {
+        (Vec::new(), None)
+    }
+
+    /// The players in the room that the current step is waiting on
+    fn waiting_on(&self, room: &RoomHandle) -> Vec<usize>;
+
+    /// Ends the current step whether or not everyone has finished it
+    fn finish_step(&mut self, room: &mut RoomHandle) -> Next;
+}
+
+/// The rules of a game. `Room` handles everything else such as the connections,
+/// canvas and timers, and asks the game mode whenever a rule needs deciding.
+///
+/// Every method has a default which is how the classic game plays, so a new mode
+/// only needs to override the rules that it changes.
+pub trait GameMode {
+    /// The game if everyone plays at once, which then runs itself instead of
+    /// `Room` playing rounds where players take turns drawing
+    fn solo_game(&self) -> Option<&dyn SoloGame> {
+        None
+    }
+
+    fn solo_game_mut(&mut self) -> Option<&mut dyn SoloGame> {
+        None
+    }
+
+    /// How many players are needed to start a game
+    fn min_players(&self) -> usize {
+        1
+    }
+
+    /// Called when the game starts with everyone in the room
+    fn start_game(&mut self, _players: &[usize]) {}
+
+    /// Called when someone joins the room part way through a game
+    fn player_joined(&mut self, _session_id: usize) {}
+
+    /// Called when someone leaves the room part way through a game
+    fn player_left(&mut self, _session_id: usize) {}
+
+    /// Picks the word for a new round
+    fn choose_word(&mut self, words: &mut WordChooser) -> (usize, usize) {
+        words.random_word()
+    }
+
+    /// Picks who draws in a new round, removing them from the queue. Everyone picked
+    /// draws on the same canvas, and none of them can be picked if nobody is left to
+    /// guess. The leaders of the last round have already been put back in by
+    /// `finish_round`.
+    fn choose_leaders(
+        &mut self,
+        queue: &mut VecDeque<usize>,
+        occupants: &HashMap<usize, Occupant>,
+    ) -> Vec<usize> {
+        while let Some(leader) = queue.pop_front() {
+            if occupants.contains_key(&leader) {
+                return vec![leader];
+            }
+        }
+        Vec::new()
+    }
+
+    /// Decides what a chat message sent during a round means. Only the leaders
+    /// know the word so they can't guess it, and can't say it either.
+    fn evaluate_guess(
+        &self,
+        session_id: usize,
+        leaders: &[usize],
+        word_pack: &WordPack,
+        word: usize,
+        message: &str,
+    ) -> Guess {
+        if leaders.contains(&session_id) {
+            if word_pack.word_appears_in(word, message) {
+                Guess::Blocked
+            } else {
+                Guess::Chat
+            }
+        } else {
+            match word_pack.word_matches(word, &message.trim().to_lowercase()) {
+                (true, alternate) => Guess::Correct(alternate),
+                (false, _) => Guess::Chat,
+            }
+        }
+    }
+
+    /// Gives out points for a correct guess and returns the points shown with the
+    /// winner, or `None` if the winner isn't in the room
+    fn score(
+        &mut self,
+        winner: usize,
+        _leaders: &[usize],
+        occupants: &mut HashMap<usize, Occupant>,
+    ) -> Option<usize> {
+        let (_, _, points) = occupants.get_mut(&winner)?;
+        *points += 1;
+        Some(*points)
+    }
+
+    /// Called when a round ends, putting the leaders back in the queue so that
+    /// everyone gets a turn
+    fn finish_round(&mut self, leaders: &[usize], queue: &mut VecDeque<usize>) {
+        queue.extend(leaders);
+    }
+
+    /// How long the word is shown for before the next round starts
+    fn intermission(&self, timers: &TimersConfig) -> Duration {
+        timers.intermission()
+    }
+
+    /// Whether the game ends instead of starting another round
+    fn is_game_over(&self, rounds_played: usize, round_limit: Option<usize>) -> bool {
+        matches!(round_limit, Some(limit) if rounds_played >= limit)
+    }
+
+    /// The score of each team, empty if the mode doesn't have teams
+    fn scoreboard(&self) -> Vec<TeamScore> {
+        Vec::new()
+    }
+}
+
+/// The original game: players take turns drawing and the first to guess gets a point
+pub struct Classic;
+
+impl GameMode for Classic {}
+
+/// A game mode that the host can pick in the lobby
+pub struct GameModeInfo {
+    pub name: &'static str,
+    pub description: &'static str,
+    create: fn(&str) -> Option<Box<dyn GameMode>>,
+}
+
+impl GameModeInfo {
+    /// Creates the mode for a new game from the options the host picked, which
+    /// each mode is free to interpret. Returns `None` if the options are invalid.
+    pub fn create(&self, options: &str) -> Option<Box<dyn GameMode>> {
+        (self.create)(options)
+    }
+}
+
+/// Every game mode, the id of a mode is its index and the first is the default
+pub const GAME_MODES: &[GameModeInfo] = &[
+    GameModeInfo {
+        name: "Classic",
+        description: "Take turns drawing, the first to guess the word gets a point",
+        create: |_| Some(Box::new(Classic)),
+    },
+    GameModeInfo {
+        name: "Teams",
+        description: "Teams take turns drawing and only the drawing team can guess",
+        create: |options| Some(Box::new(Teams::from_options(options)?)),
+    },
+    GameModeInfo {
+        name: "Telephone",
+        description: "Everyone writes a prompt, then drawings and descriptions are passed along",
+        create: |_| Some(Box::new(Telephone::default())),
+    },
+    GameModeInfo {
+        name: "Draw Off",
+        description: "Everyone draws the same word at once, then votes for the best drawing",
+        create: |_| Some(Box::new(DrawOff::default())),
+    },
+    GameModeInfo {
+        name: "Collaborative",
+        description: "Several players draw the word together on one canvas",
+        create: |options| Some(Box::new(Collaborative::from_options(options)?)),
+    },
+    GameModeInfo {
+        name: "Reverse",
+        description: "One player describes the word while everyone else draws it",
+        create: |options| Some(Box::new(Reverse::from_options(options)?)),
+    },
+];
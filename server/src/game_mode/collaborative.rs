@@ -0,0 +1,49 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{game_mode::GameMode, room::Occupant};
+
+const MAX_DRAWERS: usize = 4;
+
+/// Several players draw the word together on one canvas while everyone else guesses
+pub struct Collaborative {
+    drawers: usize,
+}
+
+impl Collaborative {
+    /// Parses the number of players drawing each round, blank options mean two
+    pub fn from_options(options: &str) -> Option<Collaborative> {
+        let drawers = match options.trim() {
+            "" => 2,
+            drawers => drawers.parse().ok()?,
+        };
+        if (2..=MAX_DRAWERS).contains(&drawers) {
+            Some(Collaborative { drawers })
+        } else {
+            None
+        }
+    }
+}
+
+impl GameMode for Collaborative {
+    fn min_players(&self) -> usize {
+        3
+    }
+
+    /// Takes the next players in the queue, leaving at least one person to guess
+    fn choose_leaders(
+        &mut self,
+        queue: &mut VecDeque<usize>,
+        occupants: &HashMap<usize, Occupant>,
+    ) -> Vec<usize> {
+        let drawers = self.drawers.min(occupants.len().saturating_sub(1)).max(1);
+        let mut leaders = Vec::with_capacity(drawers);
+        while leaders.len() < drawers {
+            match queue.pop_front() {
+                Some(leader) if occupants.contains_key(&leader) => leaders.push(leader),
+                Some(_) => {}
+                None => break,
+            }
+        }
+        leaders
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{
+    game_mode::{voting::VotingRound, GameMode, Next, Prompt, SoloGame},
+    metrics::StateName,
+    room::RoomHandle,
+    Event,
+};
+
+/// Everyone draws the same word at once on their own canvas, then everyone votes for
+/// their favourite drawing and each vote is worth a point
+#[derive(Default)]
+pub struct DrawOff {
+    round: Option<VotingRound>,
+}
+
+impl DrawOff {
+    fn round(&self) -> &VotingRound {
+        self.round
+            .as_ref()
+            .expect("draw off hadn't started a round")
+    }
+
+    fn round_mut(&mut self) -> &mut VotingRound {
+        self.round
+            .as_mut()
+            .expect("draw off hadn't started a round")
+    }
+
+    /// Tells everyone to draw the word
+    fn drawing_event(&self, room: &RoomHandle) -> Event {
+        let round = self.round();
+        let prompt = Prompt::Text(room.word(round.word()).to_string());
+        Event::SoloStep(true, Some(prompt), round.timeout())
+    }
+}
+
+impl GameMode for DrawOff {
+    fn min_players(&self) -> usize {
+        2
+    }
+
+    fn solo_game(&self) -> Option<&dyn SoloGame> {
+        Some(self)
+    }
+
+    fn solo_game_mut(&mut self) -> Option<&mut dyn SoloGame> {
+        Some(self)
+    }
+}
+
+impl SoloGame for DrawOff {
+    fn state_name(&self) -> StateName {
+        self.round().state_name()
+    }
+
+    /// Gives everyone in the room a canvas to draw the word on
+    fn new_round(&mut self, room: &mut RoomHandle) -> Next {
+        self.round = Some(VotingRound::start(&room.players(), &[], room));
+        room.broadcast(self.drawing_event(room));
+        Next::Continue
+    }
+
+    fn joined(&mut self, session_id: usize, room: &mut RoomHandle) {
+        let drawing = self.drawing_event(room);
+        self.round().joined(session_id, drawing, room);
+    }
+
+    /// Hands in the player's drawing, the content is ignored
+    fn submit(&mut self, session_id: usize, _content: &str, room: &mut RoomHandle) -> bool {
+        self.round_mut().submit(session_id, room)
+    }
+
+    fn vote(&mut self, session_id: usize, data: &str, room: &mut RoomHandle) -> bool {
+        self.round_mut().vote(session_id, data, room)
+    }
+
+    fn describe_round(&self, room: &RoomHandle) -> (Vec<usize>, Option<String>) {
+        let word = room.word(self.round().word()).to_string();
+        (Vec::new(), Some(word))
+    }
+
+    fn waiting_on(&self, room: &RoomHandle) -> Vec<usize> {
+        self.round().waiting_on(room)
+    }
+
+    fn finish_step(&mut self, room: &mut RoomHandle) -> Next {
+        self.round_mut().finish_step(None, room)
+    }
+}
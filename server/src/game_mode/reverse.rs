@@ -0,0 +1,159 @@
+use log::info;
+
+use crate::{
+    game_mode::{voting::VotingRound, GameMode, Next, SoloGame},
+    metrics::StateName,
+    room::RoomHandle,
+    Event,
+};
+
+/// Players take turns describing the word in the chat while everyone else draws
+/// it, and the one describing picks the closest drawing
+pub struct Reverse {
+    /// Whether clues can't use any of the letters in the word, rather than just
+    /// the word itself
+    no_letters: bool,
+    /// Who is describing the word this round
+    clue_giver: usize,
+    round: Option<VotingRound>,
+}
+
+impl Reverse {
+    /// Parses which clues are allowed, either `word` to only block the word or
+    /// `letters` to block every letter in it. Blank options mean `word`.
+    pub fn from_options(options: &str) -> Option<Reverse> {
+        let no_letters = match options.trim() {
+            "" | "word" => false,
+            "letters" => true,
+            _ => return None,
+        };
+        Some(Reverse {
+            no_letters,
+            clue_giver: 0,
+            round: None,
+        })
+    }
+
+    fn round(&self) -> &VotingRound {
+        self.round.as_ref().expect("reverse hadn't started a round")
+    }
+
+    fn round_mut(&mut self) -> &mut VotingRound {
+        self.round.as_mut().expect("reverse hadn't started a round")
+    }
+
+    /// Whether the clue giver can send the message. Either way they can't say the
+    /// word.
+    fn allows_clue(&self, message: &str, room: &RoomHandle) -> bool {
+        let (pack, word) = self.round().word();
+        let word_pack = room.word_pack(pack);
+        if self.no_letters {
+            let word = word_pack.get_word(word).to_lowercase();
+            !message
+                .to_lowercase()
+                .chars()
+                .any(|c| c.is_alphabetic() && word.contains(c))
+        } else {
+            !word_pack.word_appears_in(word, message)
+        }
+    }
+}
+
+impl GameMode for Reverse {
+    fn min_players(&self) -> usize {
+        2
+    }
+
+    fn solo_game(&self) -> Option<&dyn SoloGame> {
+        Some(self)
+    }
+
+    fn solo_game_mut(&mut self) -> Option<&mut dyn SoloGame> {
+        Some(self)
+    }
+}
+
+impl SoloGame for Reverse {
+    fn state_name(&self) -> StateName {
+        self.round().state_name()
+    }
+
+    /// Gives the next player in the queue the word to describe, and everyone else
+    /// a canvas to draw it on. Nobody else is told what it is.
+    fn new_round(&mut self, room: &mut RoomHandle) -> Next {
+        let players = room.players();
+        let clue_giver = match room.take_turn() {
+            Some(clue_giver) => clue_giver,
+            None => {
+                info!(
+                    "Room {} had no possible clue giver for new round, ending the game",
+                    room.key()
+                );
+                return Next::EndGame;
+            }
+        };
+        self.clue_giver = clue_giver;
+        let drawers: Vec<_> = players
+            .iter()
+            .copied()
+            .filter(|id| *id != clue_giver)
+            .collect();
+        let round = VotingRound::start(&drawers, &[clue_giver], room);
+        for session_id in players {
+            let word =
+                Some(room.word(round.word()).to_string()).filter(|_| session_id == clue_giver);
+            room.send(
+                session_id,
+                Event::ReverseStep(clue_giver, word, round.timeout()),
+            );
+        }
+        self.round = Some(round);
+        Next::Continue
+    }
+
+    fn joined(&mut self, session_id: usize, room: &mut RoomHandle) {
+        let drawing = Event::ReverseStep(self.clue_giver, None, self.round().timeout());
+        self.round().joined(session_id, drawing, room);
+    }
+
+    /// There's nothing left to draw if the word isn't being described
+    fn left(&mut self, session_id: usize, _room: &mut RoomHandle) -> bool {
+        session_id == self.clue_giver && self.round().is_drawing()
+    }
+
+    /// Hands in the player's drawing, the content is ignored
+    fn submit(&mut self, session_id: usize, _content: &str, room: &mut RoomHandle) -> bool {
+        self.round_mut().submit(session_id, room)
+    }
+
+    fn vote(&mut self, session_id: usize, data: &str, room: &mut RoomHandle) -> bool {
+        self.round_mut().vote(session_id, data, room)
+    }
+
+    /// Keeps clues that break the rules from everyone else
+    fn blocks_message(&self, session_id: usize, message: &str, room: &RoomHandle) -> bool {
+        session_id == self.clue_giver
+            && self.round().is_drawing()
+            && !self.allows_clue(message, room)
+    }
+
+    fn describe_round(&self, room: &RoomHandle) -> (Vec<usize>, Option<String>) {
+        let word = room.word(self.round().word()).to_string();
+        (vec![self.clue_giver], Some(word))
+    }
+
+    fn waiting_on(&self, room: &RoomHandle) -> Vec<usize> {
+        self.round().waiting_on(room)
+    }
+
+    /// The clue giver picks the closest drawing, or everyone votes instead if
+    /// they've left
+    fn finish_step(&mut self, room: &mut RoomHandle) -> Next {
+        let judge = Some(self.clue_giver).filter(|id| room.is_present(*id));
+        let next = self.round_mut().finish_step(judge, room);
+        if let (Next::NextRound, Some(judge)) = (&next, judge) {
+            room.end_turn(judge);
+        }
+        next
+    }
+}
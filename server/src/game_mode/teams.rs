@@ -0,0 +1,182 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    game_mode::{GameMode, Guess, TeamScore},
+    room::Occupant,
+    word_pack::WordPack,
+};
+
+const MAX_TEAMS: usize = 8;
+
+/// Players are split into teams which take turns drawing. Only the drawing team can
+/// guess, and points go to the whole team.
+pub struct Teams {
+    /// The members of each team in the order they take turns drawing
+    teams: Vec<VecDeque<usize>>,
+    points: Vec<usize>,
+    /// Teams that the host put players in before the game started
+    assignments: HashMap<usize, usize>,
+    /// The team drawing in the current round
+    current_team: usize,
+}
+
+impl Teams {
+    /// Parses the number of teams followed by any players the host put in a team, as
+    /// `count,session_id=team,...`. Anyone not put in a team is auto-balanced, and
+    /// blank options mean two auto-balanced teams.
+    pub fn from_options(options: &str) -> Option<Teams> {
+        let mut parts = options.split(',').map(|x| x.trim());
+        let count = match parts.next() {
+            Some("") | None => 2,
+            Some(count) => count.parse().ok()?,
+        };
+        if !(2..=MAX_TEAMS).contains(&count) {
+            return None;
+        }
+        let assignments = parts
+            .map(|part| {
+                let mut assignment = part.splitn(2, '=');
+                let session_id = assignment.next()?.parse().ok()?;
+                let team = assignment.next()?.parse().ok().filter(|x| *x < count)?;
+                Some((session_id, team))
+            })
+            .collect::<Option<HashMap<_, _>>>()?;
+        Some(Teams {
+            teams: vec![VecDeque::new(); count],
+            points: vec![0; count],
+            assignments,
+            current_team: count - 1,
+        })
+    }
+
+    fn team_of(&self, session_id: usize) -> Option<usize> {
+        self.teams
+            .iter()
+            .position(|members| members.contains(&session_id))
+    }
+
+    /// Puts the player in whichever team has the fewest members
+    fn balance(&mut self, session_id: usize) {
+        if let Some(smallest) = self.teams.iter_mut().min_by_key(|members| members.len()) {
+            smallest.push_back(session_id);
+        }
+    }
+
+    /// Rotates through the teams after the current one, picking the next member of
+    /// the first team that has someone left in the room to guess
+    fn next_leader(&mut self, occupants: &HashMap<usize, Occupant>) -> Option<usize> {
+        for offset in 1..=self.teams.len() {
+            let team = (self.current_team + offset) % self.teams.len();
+            let members = &mut self.teams[team];
+            let present = members
+                .iter()
+                .filter(|id| occupants.contains_key(id))
+                .count();
+            if present < 2 {
+                continue;
+            }
+            while let Some(leader) = members.pop_front() {
+                members.push_back(leader);
+                if occupants.contains_key(&leader) {
+                    self.current_team = team;
+                    return Some(leader);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl GameMode for Teams {
+    /// Enough players that some team has someone to guess while another draws
+    fn min_players(&self) -> usize {
+        self.teams.len() + 1
+    }
+
+    fn start_game(&mut self, players: &[usize]) {
+        let mut unassigned = Vec::new();
+        for session_id in players {
+            match self.assignments.get(session_id) {
+                Some(team) => self.teams[*team].push_back(*session_id),
+                None => unassigned.push(*session_id),
+            }
+        }
+        for session_id in unassigned {
+            self.balance(session_id);
+        }
+    }
+
+    fn player_joined(&mut self, session_id: usize) {
+        if self.team_of(session_id).is_none() {
+            self.balance(session_id);
+        }
+    }
+
+    fn player_left(&mut self, session_id: usize) {
+        for members in &mut self.teams {
+            members.retain(|id| *id != session_id);
+        }
+    }
+
+    /// Teams take turns, and the members of a team take turns within it. Teams
+    /// without someone to guess are skipped, since nobody could win the round.
+    fn choose_leaders(
+        &mut self,
+        _queue: &mut VecDeque<usize>,
+        occupants: &HashMap<usize, Occupant>,
+    ) -> Vec<usize> {
+        self.next_leader(occupants).into_iter().collect()
+    }
+
+    /// Only the drawing team can guess. Everyone else can chat, but not say the word
+    /// since that would give it away to the drawing team.
+    fn evaluate_guess(
+        &self,
+        session_id: usize,
+        leaders: &[usize],
+        word_pack: &WordPack,
+        word: usize,
+        message: &str,
+    ) -> Guess {
+        if leaders.contains(&session_id) || self.team_of(session_id) != Some(self.current_team) {
+            if word_pack.word_appears_in(word, message) {
+                Guess::Blocked
+            } else {
+                Guess::Chat
+            }
+        } else {
+            match word_pack.word_matches(word, &message.trim().to_lowercase()) {
+                (true, alternate) => Guess::Correct(alternate),
+                (false, _) => Guess::Chat,
+            }
+        }
+    }
+
+    /// The guesser gets a point of their own, and the team's points are shown with
+    /// the winner
+    fn score(
+        &mut self,
+        winner: usize,
+        _leaders: &[usize],
+        occupants: &mut HashMap<usize, Occupant>,
+    ) -> Option<usize> {
+        let (_, _, points) = occupants.get_mut(&winner)?;
+        *points += 1;
+        self.points[self.current_team] += 1;
+        Some(self.points[self.current_team])
+    }
+
+    /// Leaders are picked from the teams rather than the room's queue
+    fn finish_round(&mut self, _leaders: &[usize], _queue: &mut VecDeque<usize>) {}
+
+    fn scoreboard(&self) -> Vec<TeamScore> {
+        self.teams
+            .iter()
+            .zip(&self.points)
+            .map(|(members, points)| TeamScore {
+                points: *points,
+                members: members.iter().copied().collect(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,376 @@
+use log::{trace, warn};
+
+use crate::{
+    game_mode::{GameMode, Next, Prompt, SoloGame},
+    metrics::StateName,
+    room::RoomHandle,
+    Event,
+};
+
+/// Players write a prompt, the next player draws it, the next describes the drawing
+/// and so on until every chain has been passed all the way round
+#[derive(Default)]
+pub struct Telephone {
+    chains: Chains,
+}
+
+impl Telephone {
+    /// Starts the current step, giving everyone the latest entry of their chain to
+    /// draw or describe
+    fn start_step(&mut self, room: &mut RoomHandle) {
+        let drawing = self.chains.is_drawing_step();
+        let limit = if drawing {
+            room.config().timers.drawing_limit()
+        } else {
+            room.config().timers.telephone_write_limit()
+        };
+        let timeout = room.start_step(limit);
+        let players: Vec<_> = self
+            .chains
+            .players()
+            .iter()
+            .copied()
+            .filter(|id| room.is_present(*id))
+            .collect();
+        room.open_canvases(if drawing { &players } else { &[] });
+        for session_id in players {
+            let prompt = match self.chains.previous_entry(session_id) {
+                Some(Entry::Text(text)) => Some(Prompt::Text(text.clone())),
+                Some(Entry::Drawing(round)) => Some(Prompt::Drawing(room.drawing(*round).to_vec())),
+                _ => None,
+            };
+            room.send(session_id, Event::SoloStep(drawing, prompt, timeout));
+        }
+    }
+
+    /// Adds the player's canvas to their chain, empty canvases count as skipped
+    fn submit_drawing(&mut self, session_id: usize, room: &mut RoomHandle) {
+        let word = match self.chains.previous_entry(session_id) {
+            Some(Entry::Text(text)) => text.clone(),
+            _ => String::new(),
+        };
+        let entry = match room.keep_canvas(session_id, word) {
+            Some(round) => Entry::Drawing(round),
+            None => Entry::Skipped,
+        };
+        self.chains.submit(session_id, entry);
+    }
+
+    /// Adds what the player wrote to their chain if it's a valid prompt or
+    /// description, returning whether it was
+    fn submit_text(&mut self, session_id: usize, content: &str, room: &mut RoomHandle) -> bool {
+        let text = content.trim();
+        let length = text.chars().count();
+        if text.is_empty() || length > room.config().limits.max_prompt_length {
+            warn!(
+                "{} in room {} submitted text of invalid length {}",
+                session_id,
+                room.key(),
+                length
+            );
+            return false;
+        }
+        // Text is sent on a line of its own, so it can't have line breaks
+        if text.chars().any(char::is_control) {
+            warn!(
+                "{} in room {} submitted text with control characters",
+                session_id,
+                room.key()
+            );
+            return false;
+        }
+        let entry = Entry::Text(room.word_filter().censor(text));
+        self.chains.submit(session_id, entry)
+    }
+}
+
+impl GameMode for Telephone {
+    fn min_players(&self) -> usize {
+        2
+    }
+
+    fn solo_game(&self) -> Option<&dyn SoloGame> {
+        Some(self)
+    }
+
+    fn solo_game_mut(&mut self) -> Option<&mut dyn SoloGame> {
+        Some(self)
+    }
+}
+
+impl SoloGame for Telephone {
+    fn state_name(&self) -> StateName {
+        StateName::Telephone
+    }
+
+    /// The whole game is played in one go, with a chain started by everyone in the
+    /// room
+    fn new_round(&mut self, room: &mut RoomHandle) -> Next {
+        self.chains = Chains::new(room.players());
+        trace!(
+            "Room {} started telephone game with {} players",
+            room.key(),
+            self.chains.players().len()
+        );
+        self.start_step(room);
+        Next::Continue
+    }
+
+    /// They watch until the next game since the chains were decided at the start
+    fn joined(&mut self, session_id: usize, room: &mut RoomHandle) {
+        let waiting = self.waiting_on(room);
+        room.send(session_id, Event::Waiting(waiting));
+    }
+
+    /// In writing steps the content is what they wrote, in drawing steps it's
+    /// ignored and their canvas is used
+    fn submit(&mut self, session_id: usize, content: &str, room: &mut RoomHandle) -> bool {
+        if !self.chains.is_waiting_on(session_id) {
+            warn!(
+                "{} in room {} submitted when the telephone step wasn't waiting on them",
+                session_id,
+                room.key()
+            );
+            return false;
+        }
+        if self.chains.is_drawing_step() {
+            self.submit_drawing(session_id, room);
+            true
+        } else {
+            self.submit_text(session_id, content, room)
+        }
+    }
+
+    fn waiting_on(&self, room: &RoomHandle) -> Vec<usize> {
+        let mut waiting: Vec<_> = self
+            .chains
+            .players()
+            .iter()
+            .copied()
+            .filter(|id| room.is_present(*id) && self.chains.is_waiting_on(*id))
+            .collect();
+        waiting.sort_unstable();
+        waiting
+    }
+
+    /// Moves every chain on to the next player, revealing them all once they've
+    /// been passed all the way round
+    fn finish_step(&mut self, room: &mut RoomHandle) -> Next {
+        // Anyone still drawing hands in what they have so far
+        for session_id in room.drawing_players() {
+            self.submit_drawing(session_id, room);
+        }
+        self.chains.next_step();
+        if self.chains.is_finished() {
+            room.broadcast(Event::TelephoneChains(self.chains.chains().to_vec()));
+            Next::EndGame
+        } else {
+            self.start_step(room);
+            Next::Continue
+        }
+    }
+}
+
+/// Something a player added to a chain
+#[derive(Clone, Debug)]
+pub enum Entry {
+    Text(String),
+    /// The index of the drawing in the room's finished rounds
+    Drawing(usize),
+    /// The player ran out of time or left before adding anything
+    Skipped,
+}
+
+/// Every chain in a game of telephone. At each step every chain is passed to the
+/// next player, so chain `i` is worked on by player `i + step`.
+#[derive(Default)]
+struct Chains {
+    /// The players in the order chains are passed along
+    players: Vec<usize>,
+    /// The author and entry of each step of each chain, chain `i` was started by
+    /// player `i`
+    chains: Vec<Vec<(usize, Entry)>>,
+    step: usize,
+}
+
+impl Chains {
+    fn new(players: Vec<usize>) -> Chains {
+        Chains {
+            chains: vec![Vec::new(); players.len()],
+            players,
+            step: 0,
+        }
+    }
+
+    fn players(&self) -> &[usize] {
+        &self.players
+    }
+
+    fn chains(&self) -> &[Vec<(usize, Entry)>] {
+        &self.chains
+    }
+
+    /// Steps alternate between writing and drawing, starting with writing a prompt
+    fn is_drawing_step(&self) -> bool {
+        self.step % 2 == 1
+    }
+
+    /// Whether every chain has been passed all the way round
+    fn is_finished(&self) -> bool {
+        self.step >= self.players.len()
+    }
+
+    /// The chain that the player adds to in the current step
+    fn chain_of(&self, player: usize) -> Option<usize> {
+        let position = self.players.iter().position(|id| *id == player)?;
+        let len = self.players.len();
+        Some((position + len - self.step % len) % len)
+    }
+
+    /// The latest entry of the player's chain that wasn't skipped, which is what
+    /// they draw or describe
+    fn previous_entry(&self, player: usize) -> Option<&Entry> {
+        self.chains[self.chain_of(player)?]
+            .iter()
+            .rev()
+            .map(|(_, entry)| entry)
+            .find(|entry| !matches!(entry, Entry::Skipped))
+    }
+
+    /// Whether the player still has to add to their chain in this step
+    fn is_waiting_on(&self, player: usize) -> bool {
+        matches!(self.chain_of(player), Some(chain) if self.chains[chain].len() == self.step)
+    }
+
+    /// Adds the player's entry to their chain, returning false if they already have
+    fn submit(&mut self, player: usize, entry: Entry) -> bool {
+        if let Some(chain) = self.chain_of(player) {
+            if self.chains[chain].len() == self.step {
+                self.chains[chain].push((player, entry));
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Moves on to the next step, skipping anyone who hasn't added to their chain
+    fn next_step(&mut self) {
+        for (i, chain) in self.chains.iter_mut().enumerate() {
+            if chain.len() == self.step {
+                let player = self.players[(i + self.step) % self.players.len()];
+                chain.push((player, Entry::Skipped));
+            }
+        }
+        self.step += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each chain as the author and a short description of each entry
+    fn summary(chains: &Chains) -> Vec<Vec<(usize, String)>> {
+        chains
+            .chains()
+            .iter()
+            .map(|chain| {
+                chain
+                    .iter()
+                    .map(|(author, entry)| {
+                        let entry = match entry {
+                            Entry::Text(text) => text.clone(),
+                            Entry::Drawing(round) => format!("#{}", round),
+                            Entry::Skipped => "-".to_string(),
+                        };
+                        (*author, entry)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn previous_text(chains: &Chains, player: usize) -> Option<&str> {
+        match chains.previous_entry(player) {
+            Some(Entry::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn two_players_swap_chains() {
+        let mut chains = Chains::new(vec![10, 20]);
+        assert!(!chains.is_drawing_step());
+        assert!(chains.previous_entry(10).is_none());
+        assert!(chains.submit(10, Entry::Text("cat".to_string())));
+        assert!(chains.submit(20, Entry::Text("dog".to_string())));
+        assert!(!chains.submit(20, Entry::Text("again".to_string())));
+        chains.next_step();
+
+        assert!(chains.is_drawing_step());
+        assert_eq!(previous_text(&chains, 10), Some("dog"));
+        assert_eq!(previous_text(&chains, 20), Some("cat"));
+        assert!(chains.submit(10, Entry::Drawing(0)));
+        assert!(chains.submit(20, Entry::Drawing(1)));
+        assert!(!chains.is_finished());
+        chains.next_step();
+
+        assert!(chains.is_finished());
+        assert_eq!(
+            summary(&chains),
+            [
+                [(10, "cat".to_string()), (20, "#1".to_string())],
+                [(20, "dog".to_string()), (10, "#0".to_string())],
+            ]
+        );
+    }
+
+    #[test]
+    fn three_players_pass_chains_along() {
+        let mut chains = Chains::new(vec![1, 2, 3]);
+        for (player, text) in [(1, "a"), (2, "b"), (3, "c")].iter() {
+            assert!(chains.submit(*player, Entry::Text(text.to_string())));
+        }
+        chains.next_step();
+
+        // Each player gets the chain of the player before them
+        assert_eq!(previous_text(&chains, 1), Some("c"));
+        assert_eq!(previous_text(&chains, 2), Some("a"));
+        assert_eq!(previous_text(&chains, 3), Some("b"));
+        // Player 2 runs out of time without drawing anything
+        assert!(chains.submit(1, Entry::Drawing(0)));
+        assert!(chains.submit(3, Entry::Drawing(1)));
+        assert!(chains.is_waiting_on(2));
+        assert!(!chains.is_waiting_on(1));
+        chains.next_step();
+
+        // Whoever gets the skipped chain describes the prompt from before it
+        assert!(matches!(chains.previous_entry(1), Some(Entry::Drawing(1))));
+        assert!(matches!(chains.previous_entry(2), Some(Entry::Drawing(0))));
+        assert_eq!(previous_text(&chains, 3), Some("a"));
+        for player in 1..=3 {
+            assert!(chains.is_waiting_on(player));
+            assert!(chains.submit(player, Entry::Text(format!("{}'s guess", player))));
+        }
+        chains.next_step();
+
+        assert!(chains.is_finished());
+        let entry = |author: usize, entry: &str| (author, entry.to_string());
+        assert_eq!(
+            summary(&chains),
+            [
+                [entry(1, "a"), entry(2, "-"), entry(3, "3's guess")],
+                [entry(2, "b"), entry(3, "#1"), entry(1, "1's guess")],
+                [entry(3, "c"), entry(1, "#0"), entry(2, "2's guess")],
+            ]
+        );
+    }
+
+    #[test]
+    fn players_not_in_the_game_have_no_chain() {
+        let mut chains = Chains::new(vec![1, 2]);
+        assert!(!chains.is_waiting_on(3));
+        assert!(!chains.submit(3, Entry::Text("late".to_string())));
+        assert!(chains.previous_entry(3).is_none());
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+
+use log::{trace, warn};
+
+use crate::{event_log::GameEvent, game_mode::Next, metrics::StateName, room::RoomHandle, Event};
+
+/// Where a round where everyone draws the same word is up to
+enum Stage {
+    /// Everyone is drawing the word, with the author and index in the finished
+    /// rounds of each drawing handed in
+    Drawing(Vec<(usize, usize)>),
+    Voting(Ballot),
+    /// The author, votes and total points of each drawing
+    Results(Vec<(usize, usize, usize)>),
+}
+
+/// A round where everyone draws the same word at once on their own canvas, then
+/// votes on the drawings. Used by the game modes played this way.
+pub struct VotingRound {
+    word: (usize, usize),
+    stage: Stage,
+    /// When the current stage times out, if the round timer is on
+    timeout: Option<u128>,
+}
+
+impl VotingRound {
+    /// Picks the word and gives each of the drawers a canvas to draw it on, leaving
+    /// it to the game mode to tell everyone
+    pub fn start(drawers: &[usize], leaders: &[usize], room: &mut RoomHandle) -> VotingRound {
+        let word = room.choose_word();
+        let timeout = room.start_step(room.config().timers.drawing_limit());
+        room.open_canvases(drawers);
+        room.metrics().round_started();
+        room.record(GameEvent::RoundStart {
+            round: room.round_id(),
+            leaders,
+            word: room.word(word),
+        });
+        trace!(
+            "Room {} has new round where everyone draws {:?}",
+            room.key(),
+            word
+        );
+        VotingRound {
+            word,
+            stage: Stage::Drawing(Vec::new()),
+            timeout,
+        }
+    }
+
+    pub fn word(&self) -> (usize, usize) {
+        self.word
+    }
+
+    pub fn timeout(&self) -> Option<u128> {
+        self.timeout
+    }
+
+    pub fn is_drawing(&self) -> bool {
+        matches!(self.stage, Stage::Drawing(_))
+    }
+
+    pub fn state_name(&self) -> StateName {
+        match self.stage {
+            Stage::Drawing(_) => StateName::Simultaneous,
+            Stage::Voting(_) => StateName::Voting,
+            Stage::Results(_) => StateName::VoteResults,
+        }
+    }
+
+    /// Shows someone who joined part way through what's going on. While everyone
+    /// is drawing they get a canvas and `drawing` is sent to them.
+    pub fn joined(&self, session_id: usize, drawing: Event, room: &mut RoomHandle) {
+        let word = room.word(self.word).to_string();
+        let event = match &self.stage {
+            Stage::Drawing(_) => {
+                room.give_canvas(session_id);
+                drawing
+            }
+            Stage::Voting(ballot) => ballot.event(&word, self.timeout, room),
+            Stage::Results(results) => Event::VoteResults(word, results.clone()),
+        };
+        room.send(session_id, event);
+    }
+
+    /// Hands in the player's drawing to be voted on
+    fn hand_in(&mut self, session_id: usize, room: &mut RoomHandle) {
+        if let Stage::Drawing(drawings) = &mut self.stage {
+            let word = room.word(self.word).to_string();
+            if let Some(round) = room.keep_canvas(session_id, word) {
+                drawings.push((session_id, round));
+            }
+        }
+    }
+
+    /// Handles a player handing in their drawing, returning whether they could
+    pub fn submit(&mut self, session_id: usize, room: &mut RoomHandle) -> bool {
+        if !self.is_drawing() || !room.has_canvas(session_id) {
+            warn!(
+                "{} in room {} submitted when they weren't drawing",
+                session_id,
+                room.key()
+            );
+            return false;
+        }
+        self.hand_in(session_id, room);
+        true
+    }
+
+    pub fn vote(&mut self, session_id: usize, data: &str, room: &mut RoomHandle) -> bool {
+        match &mut self.stage {
+            Stage::Voting(ballot) => ballot.vote(session_id, data, room.key()),
+            _ => {
+                warn!(
+                    "vote sent by {} in room {} when nobody was voting",
+                    session_id,
+                    room.key()
+                );
+                false
+            }
+        }
+    }
+
+    pub fn waiting_on(&self, room: &RoomHandle) -> Vec<usize> {
+        match &self.stage {
+            Stage::Drawing(_) => room.drawing_players(),
+            Stage::Voting(ballot) => ballot.waiting_on(room),
+            Stage::Results(_) => Vec::new(),
+        }
+    }
+
+    /// Ends the current stage: voting starts once drawing has finished, and the
+    /// votes are counted once voting has finished. Everyone votes when there isn't
+    /// a judge.
+    pub fn finish_step(&mut self, judge: Option<usize>, room: &mut RoomHandle) -> Next {
+        match self.stage {
+            Stage::Drawing(_) => self.start_voting(judge, room),
+            Stage::Voting(_) => self.finish_voting(room),
+            // The next round has already been scheduled
+            Stage::Results(_) => Next::Continue,
+        }
+    }
+
+    /// Shows everyone the drawings so that they can be voted on. Anyone still
+    /// drawing hands in what they have so far.
+    fn start_voting(&mut self, judge: Option<usize>, room: &mut RoomHandle) -> Next {
+        for session_id in room.drawing_players() {
+            self.hand_in(session_id, room);
+        }
+        let drawings = match &mut self.stage {
+            Stage::Drawing(drawings) => std::mem::take(drawings),
+            _ => return Next::Continue,
+        };
+        let ballot = Ballot::new(drawings, judge);
+        if !ballot.has_enough_drawings() {
+            trace!("Room {} didn't have enough drawings to vote on", room.key());
+            self.stage = Stage::Voting(ballot);
+            return self.finish_voting(room);
+        }
+        self.timeout = room.start_step(room.config().timers.voting_limit());
+        room.broadcast(ballot.event(room.word(self.word), self.timeout, room));
+        self.stage = Stage::Voting(ballot);
+        Next::Continue
+    }
+
+    /// Counts the votes and shows everyone the results before the next round
+    fn finish_voting(&mut self, room: &mut RoomHandle) -> Next {
+        let results = match &self.stage {
+            Stage::Voting(ballot) => ballot.count(room),
+            _ => return Next::Continue,
+        };
+        room.round_finished();
+        let word = room.word(self.word).to_string();
+        room.broadcast(Event::VoteResults(word, results.clone()));
+        self.stage = Stage::Results(results);
+        Next::NextRound
+    }
+}
+
+/// The drawings handed in during a round where everyone draws the same word, and
+/// who voted for which
+pub struct Ballot {
+    /// The author and index in the finished rounds of each drawing
+    drawings: Vec<(usize, usize)>,
+    /// The only one who can vote, if everyone else can't
+    judge: Option<usize>,
+    /// Who each voter voted for
+    votes: HashMap<usize, usize>,
+}
+
+impl Ballot {
+    pub fn new(drawings: Vec<(usize, usize)>, judge: Option<usize>) -> Ballot {
+        Ballot {
+            drawings,
+            judge,
+            votes: HashMap::new(),
+        }
+    }
+
+    /// Whether there are enough drawings to vote on. Players can't vote for
+    /// themselves, but a judge didn't draw anything.
+    pub fn has_enough_drawings(&self) -> bool {
+        self.drawings.len() >= if self.judge.is_some() { 1 } else { 2 }
+    }
+
+    /// Shows the drawings so that they can be voted on
+    pub fn event(&self, word: &str, timeout: Option<u128>, room: &RoomHandle) -> Event {
+        let drawings = self
+            .drawings
+            .iter()
+            .map(|(author, round)| (*author, room.drawing(*round).to_vec()))
+            .collect();
+        Event::Voting(word.to_string(), drawings, self.judge, timeout)
+    }
+
+    /// Handles a vote for the drawing by the author whose session id is `data`,
+    /// returning whether it counted. Everyone gets one vote and can't vote for
+    /// themselves, unless there's a judge who is the only one who can vote.
+    pub fn vote(&mut self, session_id: usize, data: &str, room_key: &str) -> bool {
+        if matches!(self.judge, Some(judge) if judge != session_id) {
+            warn!(
+                "{} in room {} voted when they weren't the judge",
+                session_id, room_key
+            );
+            return false;
+        }
+        let author = match data.trim().parse::<usize>() {
+            Ok(author) => author,
+            Err(_) => {
+                warn!(
+                    "{} in room {} sent a vote that wasn't a session id",
+                    session_id, room_key
+                );
+                return false;
+            }
+        };
+        if author == session_id || !self.drawings.iter().any(|(id, _)| *id == author) {
+            warn!(
+                "{} in room {} voted for {} who they can't vote for",
+                session_id, room_key, author
+            );
+            return false;
+        }
+        if self.votes.contains_key(&session_id) {
+            warn!("{} in room {} voted twice", session_id, room_key);
+            return false;
+        }
+        self.votes.insert(session_id, author);
+        true
+    }
+
+    /// The players in the room who haven't voted yet
+    pub fn waiting_on(&self, room: &RoomHandle) -> Vec<usize> {
+        let voters = match self.judge {
+            Some(judge) => vec![judge],
+            None => room.players(),
+        };
+        let mut waiting: Vec<_> = voters
+            .into_iter()
+            .filter(|id| room.is_present(*id) && !self.votes.contains_key(id))
+            .collect();
+        waiting.sort_unstable();
+        waiting
+    }
+
+    /// The author and number of votes of each drawing, most votes first. Drawings
+    /// with the same number of votes stay in the order they were handed in.
+    pub fn tally(&self) -> Vec<(usize, usize)> {
+        let mut tally: Vec<_> = self
+            .drawings
+            .iter()
+            .map(|(author, _)| {
+                let count = self.votes.values().filter(|id| *id == author).count();
+                (*author, count)
+            })
+            .collect();
+        tally.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        tally
+    }
+
+    /// Gives each author a point for every vote they got. Returns the author,
+    /// number of votes and total points of each drawing, most votes first.
+    pub fn count(&self, room: &mut RoomHandle) -> Vec<(usize, usize, usize)> {
+        self.tally()
+            .into_iter()
+            .map(|(author, count)| {
+                let points = room.add_points(author, count).unwrap_or(0);
+                (author, count, points)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn everyone_votes_once_for_someone_else() {
+        let mut ballot = Ballot::new(vec![(1, 0), (2, 1), (3, 2)], None);
+        assert!(!ballot.vote(1, "1", "ROOM"));
+        assert!(!ballot.vote(1, "4", "ROOM"));
+        assert!(!ballot.vote(1, "two", "ROOM"));
+        assert!(ballot.vote(1, " 2 ", "ROOM"));
+        assert!(!ballot.vote(1, "3", "ROOM"));
+        // Players who didn't hand in a drawing can still vote
+        assert!(ballot.vote(4, "3", "ROOM"));
+        assert_eq!(ballot.votes.len(), 2);
+    }
+
+    #[test]
+    fn only_the_judge_votes_when_there_is_one() {
+        let mut ballot = Ballot::new(vec![(1, 0), (2, 1)], Some(5));
+        assert!(!ballot.vote(1, "2", "ROOM"));
+        assert!(ballot.vote(5, "1", "ROOM"));
+        assert!(!ballot.vote(5, "2", "ROOM"));
+        assert_eq!(ballot.tally(), [(1, 1), (2, 0)]);
+    }
+
+    #[test]
+    fn a_judge_needs_one_drawing_and_everyone_else_two() {
+        assert!(!Ballot::new(vec![], Some(5)).has_enough_drawings());
+        assert!(Ballot::new(vec![(1, 0)], Some(5)).has_enough_drawings());
+        assert!(!Ballot::new(vec![(1, 0)], None).has_enough_drawings());
+        assert!(Ballot::new(vec![(1, 0), (2, 1)], None).has_enough_drawings());
+    }
+
+    #[test]
+    fn the_tally_has_the_most_votes_first() {
+        let mut ballot = Ballot::new(vec![(1, 0), (2, 1), (3, 2), (4, 3)], None);
+        for (voter, author) in [(1, "3"), (2, "3"), (3, "2"), (4, "3"), (5, "2")].iter() {
+            assert!(ballot.vote(*voter, author, "ROOM"));
+        }
+        assert_eq!(ballot.tally(), [(3, 3), (2, 2), (1, 0), (4, 0)]);
+    }
+}
@@ -0,0 +1,82 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+
+use log::warn;
+use serde::Serialize;
+
+/// Writes values to a file as JSON, one per line. The writing happens on a thread
+/// of its own so that a slow disk never holds up the game server.
+pub struct JsonLines {
+    /// What the file is, used in warnings
+    name: &'static str,
+    lines: Mutex<Option<Sender<String>>>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl JsonLines {
+    pub fn new(name: &'static str, file: File) -> JsonLines {
+        let (lines, received) = mpsc::channel::<String>();
+        let writer = thread::spawn(move || {
+            let mut file = BufWriter::new(file);
+            while let Ok(line) = received.recv() {
+                let mut result = file.write_all(line.as_bytes());
+                // Writes whatever is waiting in one go, then makes sure it's on disk
+                // before waiting for more
+                for line in received.try_iter() {
+                    result = result.and_then(|_| file.write_all(line.as_bytes()));
+                }
+                if let Err(e) = result.and_then(|_| file.flush()) {
+                    warn!("Couldn't write to the {}: {}", name, e);
+                }
+            }
+        });
+        JsonLines {
+            name,
+            lines: Mutex::new(Some(lines)),
+            writer: Mutex::new(Some(writer)),
+        }
+    }
+
+    /// Queues the value to be written, doing nothing once the file is closed
+    pub fn write<T: Serialize>(&self, value: &T) {
+        let mut line = match serde_json::to_string(value) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Couldn't serialise a line of the {}: {}", self.name, e);
+                return;
+            }
+        };
+        line.push('\n');
+        if let Some(lines) = &*self.lines.lock().expect("json lines lock was poisoned") {
+            // The writer only stops once the sender is gone
+            let _ = lines.send(line);
+        }
+    }
+
+    /// Waits for everything queued to be written, then closes the file
+    pub fn close(&self) {
+        self.lines
+            .lock()
+            .expect("json lines lock was poisoned")
+            .take();
+        let writer = self
+            .writer
+            .lock()
+            .expect("json lines lock was poisoned")
+            .take();
+        if let Some(writer) = writer {
+            if writer.join().is_err() {
+                warn!("The {} writer panicked", self.name);
+            }
+        }
+    }
+}
+
+impl Drop for JsonLines {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
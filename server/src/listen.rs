@@ -0,0 +1,404 @@
+use std::future::Future;
+use std::net::{self, SocketAddr, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::{
+    fs::FileTypeExt,
+    io::{FromRawFd, IntoRawFd, RawFd},
+    net::UnixListener,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+#[cfg(unix)]
+use std::task::{Context, Poll};
+#[cfg(unix)]
+use std::time::Duration;
+use std::{fmt, io};
+
+use actix_http::{body::MessageBody, Error, HttpService, Protocol, Request, Response};
+use actix_rt::net::TcpStream;
+#[cfg(unix)]
+use actix_rt::signal::unix::{signal, SignalKind};
+use actix_server::ServerBuilder;
+use actix_service::{
+    fn_service, map_config, pipeline_factory, IntoServiceFactory, Service, ServiceFactory,
+};
+use actix_web::dev::AppConfig;
+#[cfg(unix)]
+use futures::future::poll_fn;
+#[cfg(unix)]
+use log::error;
+use log::{info, trace, warn};
+use rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+/// The first file descriptor systemd passes sockets from
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: RawFd = 3;
+/// How long to wait before accepting on a Unix socket again after it failed
+#[cfg(unix)]
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(500);
+
+/// Somewhere the server listens for connections. Unix sockets and systemd socket
+/// activation are only supported on Unix.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Address {
+    /// A host and port, with IPv6 addresses in brackets, e.g. `[::]:3007`
+    Tcp(String),
+    /// A Unix domain socket, written as `unix:/path/to/socket`
+    Unix(PathBuf),
+    /// Every socket systemd passed to the server with socket activation, written as
+    /// `systemd`
+    Systemd,
+}
+
+impl std::str::FromStr for Address {
+    type Err = String;
+
+    fn from_str(address: &str) -> Result<Address, String> {
+        if address == "systemd" {
+            Ok(Address::Systemd)
+        } else if let Some(path) = address.strip_prefix("unix:") {
+            if path.is_empty() {
+                Err("unix: needs the path to the socket after it".to_string())
+            } else {
+                Ok(Address::Unix(path.into()))
+            }
+        } else {
+            address
+                .to_socket_addrs()
+                .map_err(|e| format!("{:?} isn't a host and port: {}", address, e))?;
+            Ok(Address::Tcp(address.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Tcp(address) => write!(f, "{}", address),
+            Address::Unix(path) => write!(f, "unix:{}", path.display()),
+            Address::Systemd => write!(f, "systemd"),
+        }
+    }
+}
+
+impl Address {
+    /// The address for a host and port, which puts brackets around IPv6 hosts
+    pub fn from_host(host: &str, port: u16) -> Address {
+        match host.parse() {
+            Ok(ip) => Address::Tcp(SocketAddr::new(ip, port).to_string()),
+            Err(_) => Address::Tcp(format!("{}:{}", host, port)),
+        }
+    }
+}
+
+/// Everywhere the server listens. TCP sockets are served by actix-server's
+/// workers, but Unix sockets get an accept loop of their own since actix-server
+/// deletes a Unix socket's file whenever it stops accepting for a moment, which it
+/// does when a connection is waiting before its workers have started.
+pub struct Listeners {
+    builder: ServerBuilder,
+    /// actix-server can't be run without any sockets
+    tcp_sockets: usize,
+    /// Unix sockets the server made itself, which are deleted when it stops
+    socket_files: Vec<PathBuf>,
+}
+
+impl Listeners {
+    pub fn new(builder: ServerBuilder) -> Listeners {
+        Listeners {
+            builder,
+            tcp_sockets: 0,
+            socket_files: Vec::new(),
+        }
+    }
+
+    /// Listens on the address, using TLS for TCP sockets if there's a config for
+    /// it. Unix sockets are always plain HTTP since they're only used by proxies on
+    /// the same machine.
+    pub fn bind<F, I, S, B>(
+        mut self,
+        address: &Address,
+        tls: Option<Arc<ServerConfig>>,
+        factory: F,
+    ) -> io::Result<Listeners>
+    where
+        F: Fn() -> I + Send + Clone + 'static,
+        I: IntoServiceFactory<S>,
+        S: ServiceFactory<Config = AppConfig, Request = Request> + 'static,
+        S::Error: Into<Error> + 'static,
+        S::InitError: fmt::Debug,
+        S::Response: Into<Response<B>> + 'static,
+        <S::Service as Service>::Future: 'static,
+        B: MessageBody + 'static,
+    {
+        match address {
+            Address::Tcp(address) => {
+                self.builder = tcp(self.builder, net::TcpListener::bind(address)?, tls, factory)?;
+                self.tcp_sockets += 1;
+            }
+            #[cfg(unix)]
+            Address::Unix(path) => {
+                // A socket left behind by an earlier run has to go before binding,
+                // but anything else at the path is left alone
+                if let Ok(metadata) = std::fs::symlink_metadata(path) {
+                    if metadata.file_type().is_socket() {
+                        std::fs::remove_file(path)?;
+                    }
+                }
+                uds(UnixListener::bind(path)?, factory)?;
+                self.socket_files.push(path.clone());
+            }
+            #[cfg(unix)]
+            Address::Systemd => {
+                for listener in systemd_listeners()? {
+                    match listener {
+                        Listener::Tcp(listener) => {
+                            self.builder =
+                                tcp(self.builder, listener, tls.clone(), factory.clone())?;
+                            self.tcp_sockets += 1;
+                        }
+                        // Systemd made the socket, so it's left for systemd to
+                        // delete
+                        Listener::Unix(listener) => uds(listener, factory.clone())?,
+                    }
+                }
+            }
+            #[cfg(not(unix))]
+            Address::Unix(_) | Address::Systemd => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "can't listen on {} since it's only supported on Unix",
+                        address
+                    ),
+                ))
+            }
+        }
+        Ok(self)
+    }
+
+    /// Serves connections until the server is stopped by a signal, see
+    /// `wait_for_stop`
+    pub async fn run(self, shutdown: impl Future<Output = ()>) -> io::Result<()> {
+        let server = if self.tcp_sockets > 0 {
+            Some(self.builder.disable_signals().run())
+        } else {
+            None
+        };
+        let graceful = wait_for_stop(shutdown).await?;
+        info!("Stopping the server");
+        if let Some(server) = server {
+            server.stop(graceful).await;
+        }
+        for path in &self.socket_files {
+            if let Err(e) = std::fs::remove_file(path) {
+                warn!("Couldn't delete the socket {:?}: {}", path, e);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Waits for a signal to stop the server, returning whether it should stop
+/// gracefully. SIGINT and SIGQUIT stop it straight away, but SIGTERM runs
+/// `shutdown` first so that players can be warned, which either of the others cuts
+/// short.
+#[cfg(unix)]
+async fn wait_for_stop(shutdown: impl Future<Output = ()>) -> io::Result<bool> {
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut quit = signal(SignalKind::quit())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut stop_now =
+        |cx: &mut Context| interrupt.poll_recv(cx).is_ready() || quit.poll_recv(cx).is_ready();
+
+    let graceful = poll_fn(|cx| {
+        if stop_now(cx) {
+            Poll::Ready(false)
+        } else if terminate.poll_recv(cx).is_ready() {
+            Poll::Ready(true)
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+    if graceful {
+        info!("SIGTERM received, shutting down");
+        let mut shutdown = Box::pin(shutdown);
+        poll_fn(|cx| {
+            if stop_now(cx) || shutdown.as_mut().poll(cx).is_ready() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+    }
+    Ok(graceful)
+}
+
+/// Waits for Ctrl-C, which stops the server straight away since there's no SIGTERM
+/// to shut down gracefully with
+#[cfg(not(unix))]
+async fn wait_for_stop(_shutdown: impl Future<Output = ()>) -> io::Result<bool> {
+    actix_rt::signal::ctrl_c().await?;
+    Ok(false)
+}
+
+#[cfg(unix)]
+enum Listener {
+    Tcp(net::TcpListener),
+    Unix(UnixListener),
+}
+
+/// Takes the sockets systemd passed to the server, following `sd_listen_fds`
+#[cfg(unix)]
+fn systemd_listeners() -> io::Result<Vec<Listener>> {
+    let not_activated = || {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "systemd didn't pass any sockets to listen on",
+        )
+    };
+    let pid: u32 = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse().ok())
+        .ok_or_else(not_activated)?;
+    if pid != std::process::id() {
+        return Err(not_activated());
+    }
+    let count: RawFd = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .filter(|count| *count > 0)
+        .ok_or_else(not_activated)?;
+    // So that they aren't taken again, or by anything the server starts
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count)
+        .map(|fd| {
+            // Safe since systemd gives the server these file descriptors to own,
+            // and they're only taken once. A socket that isn't TCP has no
+            // internet address, so it must be a Unix socket.
+            let listener = unsafe { net::TcpListener::from_raw_fd(fd) };
+            match listener.local_addr() {
+                Ok(address) => {
+                    info!("Listening on {} from systemd", address);
+                    Ok(Listener::Tcp(listener))
+                }
+                Err(_) => {
+                    let listener = unsafe { UnixListener::from_raw_fd(listener.into_raw_fd()) };
+                    let address = listener.local_addr()?;
+                    info!("Listening on {:?} from systemd", address);
+                    Ok(Listener::Unix(listener))
+                }
+            }
+        })
+        .collect()
+}
+
+/// Serves HTTPS on the listener if there's a TLS config, or plain HTTP otherwise
+fn tcp<F, I, S, B>(
+    builder: ServerBuilder,
+    listener: net::TcpListener,
+    tls: Option<Arc<ServerConfig>>,
+    factory: F,
+) -> io::Result<ServerBuilder>
+where
+    F: Fn() -> I + Send + Clone + 'static,
+    I: IntoServiceFactory<S>,
+    S: ServiceFactory<Config = AppConfig, Request = Request>,
+    S::Error: Into<Error> + 'static,
+    S::InitError: fmt::Debug,
+    S::Response: Into<Response<B>> + 'static,
+    <S::Service as Service>::Future: 'static,
+    B: MessageBody + 'static,
+{
+    let tls = match tls {
+        Some(tls) => tls,
+        None => {
+            let name = format!("http-{}", listener.local_addr()?);
+            return builder.listen(name, listener, move || {
+                HttpService::build()
+                    .finish(map_config(factory(), |_| AppConfig::default()))
+                    .tcp()
+            });
+        }
+    };
+    // Connections that fail the TLS handshake are dropped
+    let name = format!("https-{}", listener.local_addr()?);
+    let acceptor = TlsAcceptor::from(tls);
+    builder.listen(name, listener, move || {
+        let acceptor = acceptor.clone();
+        pipeline_factory(fn_service(move |io: TcpStream| {
+            let peer_addr = io.peer_addr().ok();
+            let accept = acceptor.accept(io);
+            async move {
+                match accept.await {
+                    Ok(io) => Ok((io, Protocol::Http1, peer_addr)),
+                    Err(e) => {
+                        trace!("TLS handshake with {:?} failed: {}", peer_addr, e);
+                        Err(())
+                    }
+                }
+            }
+        }))
+        .and_then(
+            HttpService::build()
+                .finish(map_config(factory(), |_| AppConfig::default()))
+                .map_err(|_| ()),
+        )
+    })
+}
+
+/// Serves plain HTTP on the Unix socket for as long as the server runs
+#[cfg(unix)]
+fn uds<F, I, S, B>(listener: UnixListener, factory: F) -> io::Result<()>
+where
+    F: Fn() -> I + 'static,
+    I: IntoServiceFactory<S>,
+    S: ServiceFactory<Config = AppConfig, Request = Request> + 'static,
+    S::Error: Into<Error> + 'static,
+    S::InitError: fmt::Debug,
+    S::Response: Into<Response<B>> + 'static,
+    <S::Service as Service>::Future: 'static,
+    B: MessageBody + 'static,
+{
+    let name = format!("{:?}", listener.local_addr()?);
+    let mut listener = actix_rt::net::UnixListener::from_std(listener)?;
+    let service = HttpService::build().finish(map_config(factory(), |_| AppConfig::default()));
+    actix_rt::spawn(async move {
+        let mut service = match service.new_service(()).await {
+            Ok(service) => service,
+            Err(e) => {
+                error!("Couldn't start serving on {}: {:?}", name, e);
+                return;
+            }
+        };
+        loop {
+            let io = match listener.accept().await {
+                Ok((io, _)) => io,
+                // Most likely out of file descriptors, which will take a while to
+                // be closed
+                Err(e) => {
+                    error!("Couldn't accept a connection on {}: {}", name, e);
+                    actix_rt::time::delay_for(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            };
+            if let Err(e) = poll_fn(|cx| service.poll_ready(cx)).await {
+                error!("Couldn't serve a connection on {}: {}", name, e);
+                continue;
+            }
+            let connection = service.call((io, Protocol::Http1, None));
+            actix_rt::spawn(async move {
+                if let Err(e) = connection.await {
+                    trace!("Connection failed: {}", e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
@@ -1,20 +1,40 @@
 use actix::prelude::*;
-use actix_web::{web, App, Error, HttpRequest, HttpResponse, HttpServer};
+use actix_web::{error, web, App, Error, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
 
 use flexi_logger::{opt_format, Cleanup, Criterion, Duplicate, Logger, Naming};
 use log::info;
+use ring::constant_time::verify_slices_are_equal;
+use std::sync::Arc;
+use std::time::Duration;
 
+pub mod bot;
+pub mod config;
+pub mod draw_history;
+pub mod event_log;
+pub mod game_mode;
+pub mod json_lines;
+pub mod listen;
+pub mod metrics;
+pub mod raster;
+pub mod recording;
 pub mod room;
+pub mod room_key;
 pub mod server;
 pub mod session;
+pub mod snapshot;
+pub mod tls;
+pub mod word_filter;
 pub mod word_pack;
 
 pub use room::Room;
-pub use server::{ClientMessage, Event, GameServer};
+pub use server::{ClientMessage, DrawingRequest, Event, GameServer, MetricsRequest};
 
 use clap::{crate_authors, crate_version, load_yaml};
 
+/// How long sessions get to close their connections when the server stops
+const CLOSE_DELAY: Duration = Duration::from_secs(1);
+
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
     let yaml = load_yaml!("cli.yml");
@@ -23,8 +43,18 @@ async fn main() -> std::io::Result<()> {
         .author(crate_authors!())
         .get_matches();
 
-    if let Some(log_path) = matches.value_of("log") {
-        Logger::with_env_or_str("server=trace")
+    let config = match config::Config::load(&matches, std::env::vars()) {
+        Ok(config) => config,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}", error);
+            }
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(log_path) = &config.logging.directory {
+        Logger::with_env_or_str(config.logging.level.as_deref().unwrap_or("server=trace"))
             .directory(log_path)
             .log_to_file()
             .duplicate_to_stderr(Duplicate::Info)
@@ -36,42 +66,136 @@ async fn main() -> std::io::Result<()> {
             .format(opt_format)
             .start()
             .expect("Couldn't start logger");
+    } else if let Some(level) = &config.logging.level {
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(level)).init();
     } else {
         env_logger::init();
     }
 
-    let port = matches
-        .value_of("port")
-        .map(|x| x.parse::<u16>().expect("Port must be an integer"))
-        .unwrap_or(3007);
+    let word_filter = config
+        .game
+        .word_filter
+        .as_ref()
+        .map(|path| word_filter::WordFilter::new(path).expect("Error loading the word filter"))
+        .unwrap_or_default();
 
-    let serve_dir = matches.value_of("serve_dir").map(|x| x.to_string());
+    let event_log = config
+        .logging
+        .event_log
+        .as_ref()
+        .map(|path| event_log::EventLog::new(path).expect("Error opening the event log"))
+        .map(Arc::new)
+        .unwrap_or_default();
 
-    let word_pack_dir = matches.value_of("word_pack_dir").unwrap_or("wordpacks");
+    if let Some(replay) = matches.subcommand_matches("replay") {
+        let path = replay.value_of("recording").unwrap();
+        let differences = recording::replay(path, |seed, config, ids| {
+            GameServer::new(config, word_filter, Arc::clone(&event_log), seed)
+                .replaying(ids)
+                .start()
+        })
+        .await?;
+        event_log.close();
+        if differences.is_empty() {
+            println!("Every session was sent the same events as in the recording");
+            std::process::exit(0);
+        }
+        for difference in differences {
+            println!("{}", difference);
+        }
+        std::process::exit(1);
+    }
 
-    let game_server = server::GameServer::new(word_pack_dir).start();
+    let seed = config.game.seed.unwrap_or_else(rand::random);
+    let record = config.logging.record.clone();
+    let network = config.network.clone();
+    let admin_token = AdminToken(config.admin.token.clone());
+    let recorder = record.map(|path| {
+        recording::Recorder::new(path, seed, &config)
+            .map(Arc::new)
+            .expect("Error creating the recording")
+    });
+    let mut game_server = GameServer::new(config, word_filter, Arc::clone(&event_log), seed);
+    if let Some(recorder) = &recorder {
+        game_server = game_server.record_to(Arc::clone(recorder));
+    }
+    let game_server = game_server.start();
 
-    let serve_dir_msg = if let Some(dir) = &serve_dir {
-        format!("serving {} at '/'", dir)
+    let serve_dir_msg = if let Some(dir) = &network.serve_dir {
+        format!("serving {} at '/'", dir.display())
     } else {
         "".to_string()
     };
 
-    info!("Starting server at 0.0.0.0:{} {}", port, serve_dir_msg);
+    let tls = match (&network.tls_cert, &network.tls_key) {
+        (Some(cert), Some(key)) => {
+            let resolver = tls::CertResolver::new(cert.clone(), key.clone())
+                .map(Arc::new)
+                .unwrap_or_else(|e| {
+                    eprintln!("Couldn't load the TLS certificate: {}", e);
+                    std::process::exit(1);
+                });
+            Arc::clone(&resolver).watch();
+            Some(resolver.server_config())
+        }
+        _ => None,
+    };
+
+    let addresses = network.listen_addresses();
+    let addresses_msg: Vec<_> = addresses
+        .iter()
+        .map(|address| address.to_string())
+        .collect();
+    info!(
+        "Starting {} server at {} {}",
+        if tls.is_some() { "HTTPS" } else { "HTTP" },
+        addresses_msg.join(", "),
+        serve_dir_msg
+    );
+
+    // On SIGTERM players are warned, then their sessions are closed once the
+    // countdown is over
+    let shutdown = {
+        let game_server = game_server.clone();
+        async move {
+            if let Ok(countdown) = game_server.send(server::Shutdown).await {
+                actix_rt::time::delay_for(countdown).await;
+                let _ = game_server.send(server::CloseSessions).await;
+                // Gives the sessions a moment to send their close frames
+                actix_rt::time::delay_for(CLOSE_DELAY).await;
+            }
+        }
+    };
 
-    HttpServer::new(move || {
+    let serve_dir = network.serve_dir.clone();
+    let app = move || {
         let app = App::new()
             .data(game_server.clone())
-            .service(web::resource("/ws/").to(socket_route));
+            .data(admin_token.clone())
+            .service(web::resource("/ws/").to(socket_route))
+            .service(web::resource("/drawing/{room}/{round}").to(drawing_route))
+            .service(web::resource("/metrics").to(metrics_route));
         if let Some(dir) = &serve_dir {
             app.service(actix_files::Files::new("/", dir).index_file("index.html"))
         } else {
             app
         }
-    })
-    .bind(("0.0.0.0", port))?
-    .run()
-    .await
+    };
+
+    let mut listeners = listen::Listeners::new(actix_server::Server::build());
+    for address in &addresses {
+        listeners = listeners
+            .bind(address, tls.clone(), app.clone())
+            .map_err(|e| {
+                std::io::Error::new(e.kind(), format!("couldn't listen on {}: {}", address, e))
+            })?;
+    }
+    let result = listeners.run(shutdown).await;
+    event_log.close();
+    if let Some(recorder) = recorder {
+        recorder.close();
+    }
+    result
 }
 
 async fn socket_route(
@@ -89,3 +213,69 @@ async fn socket_route(
         stream,
     )
 }
+
+/// Exports the drawing from a finished round as a PNG. Each drawing is rasterised
+/// once on the blocking thread pool and then served from the round's record.
+///
+/// There's deliberately no check on who's asking: anyone with the room key can
+/// download any of its rounds, the same as joining the room and opening its
+/// gallery. A room's drawings are only as private as its key.
+async fn drawing_route(
+    path: web::Path<(String, usize)>,
+    game_server: web::Data<Addr<GameServer>>,
+) -> Result<HttpResponse, Error> {
+    let (room, round) = path.into_inner();
+    let record = game_server
+        .send(DrawingRequest { room, round })
+        .await
+        .map_err(error::ErrorInternalServerError)?;
+    if let Some(record) = record {
+        let png = web::block(move || record.png())
+            .await
+            .map_err(error::ErrorInternalServerError)?;
+        Ok(HttpResponse::Ok()
+            .content_type("image/png")
+            .body(png.as_ref().clone()))
+    } else {
+        Ok(HttpResponse::NotFound().finish())
+    }
+}
+
+/// The token admin pages need as a bearer token, if there is one
+#[derive(Clone)]
+struct AdminToken(Option<String>);
+
+impl AdminToken {
+    /// Whether the request has the token, compared in constant time so that it
+    /// can't be guessed a byte at a time from how long the check takes
+    fn allows(&self, req: &HttpRequest) -> bool {
+        match &self.0 {
+            Some(token) => req
+                .headers()
+                .get(actix_web::http::header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .filter(|given| verify_slices_are_equal(given.as_bytes(), token.as_bytes()).is_ok())
+                .is_some(),
+            None => true,
+        }
+    }
+}
+
+/// Exposes the server's metrics in the Prometheus text format
+async fn metrics_route(
+    req: HttpRequest,
+    admin_token: web::Data<AdminToken>,
+    game_server: web::Data<Addr<GameServer>>,
+) -> Result<HttpResponse, Error> {
+    if !admin_token.allows(&req) {
+        return Ok(HttpResponse::Unauthorized().finish());
+    }
+    let metrics = game_server
+        .send(MetricsRequest)
+        .await
+        .map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics))
+}
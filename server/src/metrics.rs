@@ -0,0 +1,188 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Declares `StateName` with the label of each state, so that the list of every
+/// state can't get out of step with the states themselves
+macro_rules! state_names {
+    ($($state:ident => $label:literal,)*) => {
+        /// What a room is doing, used as a label in the metrics
+        #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+        pub enum StateName {
+            $($state,)*
+        }
+
+        impl StateName {
+            /// Every state a room can be in
+            pub const ALL: &'static [StateName] = &[$(StateName::$state,)*];
+
+            pub fn label(self) -> &'static str {
+                match self {
+                    $(StateName::$state => $label,)*
+                }
+            }
+        }
+    };
+}
+
+state_names! {
+    Lobby => "lobby",
+    Round => "round",
+    Winner => "winner",
+    Telephone => "telephone",
+    Simultaneous => "simultaneous",
+    Voting => "voting",
+    VoteResults => "vote_results",
+}
+
+/// Counters shared between the game server and its rooms, exposed in the Prometheus
+/// text format at `/metrics`
+#[derive(Default)]
+pub struct Metrics {
+    rounds_started: AtomicUsize,
+    rounds_finished: AtomicUsize,
+    round_wins: AtomicUsize,
+    /// Steps that timed out by the state the room was in, indexed by `StateName`
+    round_timeouts: [AtomicUsize; StateName::ALL.len()],
+    draw_segments: AtomicUsize,
+    dropped_sends: AtomicUsize,
+    /// Messages from clients by type char, `a` to `z`
+    messages: [AtomicUsize; 26],
+    /// Messages from clients with any other type char
+    other_messages: AtomicUsize,
+    /// How many segments were drawn in the last whole second
+    draw_segments_per_second: AtomicUsize,
+    /// The segment count when `draw_segments_per_second` was last updated
+    last_draw_segments: AtomicUsize,
+}
+
+impl Metrics {
+    pub fn round_started(&self) {
+        self.rounds_started.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn round_finished(&self) {
+        self.rounds_finished.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn round_won(&self) {
+        self.round_wins.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn round_timed_out(&self, state: StateName) {
+        self.round_timeouts[state as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn segment_drawn(&self) {
+        self.draw_segments.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn send_dropped(&self) {
+        self.dropped_sends.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn message_received(&self, type_char: char) {
+        if type_char.is_ascii_lowercase() {
+            self.messages[(type_char as u8 - b'a') as usize].fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.other_messages.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Works out how many segments were drawn since the last tick, should be called
+    /// once a second
+    pub fn tick(&self) {
+        let total = self.draw_segments.load(Ordering::Relaxed);
+        let last = self.last_draw_segments.swap(total, Ordering::Relaxed);
+        self.draw_segments_per_second
+            .store(total - last, Ordering::Relaxed);
+    }
+
+    /// Renders every metric, along with the gauges that only the game server knows:
+    /// the number of connected sessions and the number of rooms in each state
+    pub fn render(&self, sessions: usize, rooms: &[(StateName, usize)]) -> String {
+        let mut output = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, values: &[(String, usize)]| {
+            let _ = writeln!(output, "# HELP draw_game_{} {}", name, help);
+            let _ = writeln!(output, "# TYPE draw_game_{} {}", name, kind);
+            for (labels, value) in values {
+                let _ = writeln!(output, "draw_game_{}{} {}", name, labels, value);
+            }
+        };
+        let load = |counter: &AtomicUsize| counter.load(Ordering::Relaxed);
+
+        metric(
+            "sessions",
+            "gauge",
+            "Connected sessions, including bots",
+            &[(String::new(), sessions)],
+        );
+        let rooms: Vec<_> = rooms
+            .iter()
+            .map(|(state, count)| (format!("{{state=\"{}\"}}", state.label()), *count))
+            .collect();
+        metric("rooms", "gauge", "Active rooms by state", &rooms);
+        metric(
+            "rounds_started_total",
+            "counter",
+            "Rounds started",
+            &[(String::new(), load(&self.rounds_started))],
+        );
+        metric(
+            "rounds_finished_total",
+            "counter",
+            "Rounds finished",
+            &[(String::new(), load(&self.rounds_finished))],
+        );
+        metric(
+            "round_wins_total",
+            "counter",
+            "Rounds that ended with someone guessing the word",
+            &[(String::new(), load(&self.round_wins))],
+        );
+        let timeouts: Vec<_> = StateName::ALL
+            .iter()
+            .zip(self.round_timeouts.iter())
+            .map(|(state, count)| (format!("{{state=\"{}\"}}", state.label()), load(count)))
+            .collect();
+        metric(
+            "round_timeouts_total",
+            "counter",
+            "Steps that ended because the round timer ran out, by room state",
+            &timeouts,
+        );
+        let messages: Vec<_> = (b'a'..=b'z')
+            .zip(self.messages.iter())
+            .map(|(c, count)| (format!("{{type=\"{}\"}}", c as char), load(count)))
+            .filter(|(_, count)| *count > 0)
+            .chain(std::iter::once((
+                "{type=\"other\"}".to_string(),
+                load(&self.other_messages),
+            )))
+            .collect();
+        metric(
+            "messages_total",
+            "counter",
+            "Messages received from clients by type char",
+            &messages,
+        );
+        metric(
+            "draw_segments_total",
+            "counter",
+            "Segments drawn",
+            &[(String::new(), load(&self.draw_segments))],
+        );
+        metric(
+            "draw_segments_per_second",
+            "gauge",
+            "Segments drawn in the last second",
+            &[(String::new(), load(&self.draw_segments_per_second))],
+        );
+        metric(
+            "dropped_sends_total",
+            "counter",
+            "Events that couldn't be sent because the session had disconnected",
+            &[(String::new(), load(&self.dropped_sends))],
+        );
+        output
+    }
+}
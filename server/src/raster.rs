@@ -0,0 +1,204 @@
+use crate::draw_history::{CanvasSettings, Segment, Stroke, Tool, PALETTE};
+
+const TRANSPARENT: [u8; 4] = [0, 0, 0, 0];
+
+/// An RGBA image that a drawing can be rendered to
+pub struct Bitmap {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Bitmap {
+    /// Creates a fully transparent bitmap
+    pub fn new(width: u32, height: u32) -> Bitmap {
+        Bitmap {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    /// Renders the strokes onto a blank canvas the same size as the client's
+    pub fn rasterise(canvas: CanvasSettings, strokes: &[Stroke]) -> Bitmap {
+        let mut bitmap = Bitmap::new(canvas.width as u32, canvas.height as u32);
+        for stroke in strokes {
+            bitmap.draw_stroke(stroke);
+        }
+        bitmap
+    }
+
+    pub fn draw_stroke(&mut self, stroke: &Stroke) {
+        match stroke.tool {
+            Tool::Pen(colour) => {
+                for segment in &stroke.segments {
+                    self.draw_segment(*segment, palette_colour(colour));
+                }
+            }
+            Tool::Eraser => {
+                for segment in &stroke.segments {
+                    self.draw_segment(*segment, TRANSPARENT);
+                }
+            }
+            Tool::Fill(colour) => {
+                if let Some((x, y, _, _, _)) = stroke.segments.first() {
+                    self.flood_fill(*x as u32, *y as u32, palette_colour(colour));
+                }
+            }
+        }
+    }
+
+    /// Draws a segment the same way the client does: a circle with a radius of the pen
+    /// size at the start point, and a line twice the pen size wide with flat ends
+    /// between the two points.
+    fn draw_segment(&mut self, (x1, y1, x2, y2, pen_size): Segment, colour: [u8; 4]) {
+        let radius = pen_size as f32;
+        let (x1, y1, x2, y2) = (x1 as f32, y1 as f32, x2 as f32, y2 as f32);
+        let (dx, dy) = (x2 - x1, y2 - y1);
+        let length_squared = dx * dx + dy * dy;
+
+        let min_x = (x1.min(x2) - radius).max(0.0) as u32;
+        let min_y = (y1.min(y2) - radius).max(0.0) as u32;
+        let max_x = ((x1.max(x2) + radius).ceil() as u32).min(self.width);
+        let max_y = ((y1.max(y2) + radius).ceil() as u32).min(self.height);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                // Sample from the centre of the pixel
+                let (px, py) = (x as f32 + 0.5 - x1, y as f32 + 0.5 - y1);
+                let in_circle = px * px + py * py <= radius * radius;
+                let in_line = length_squared > 0.0 && {
+                    let t = (px * dx + py * dy) / length_squared;
+                    let (ox, oy) = (px - t * dx, py - t * dy);
+                    (0.0..=1.0).contains(&t) && ox * ox + oy * oy <= radius * radius
+                };
+                if in_circle || in_line {
+                    self.set_pixel(x, y, colour);
+                }
+            }
+        }
+    }
+
+    /// Fills every pixel connected to the start point that is the same colour as it.
+    /// Goes a row at a time, only keeping the start of each run of pixels still to be
+    /// filled above and below, so that big areas don't need a huge stack.
+    fn flood_fill(&mut self, x: u32, y: u32, colour: [u8; 4]) {
+        let x = x.min(self.width - 1);
+        let y = y.min(self.height - 1);
+        let target = self.get_pixel(x, y);
+        if target == colour {
+            return;
+        }
+
+        let mut stack = vec![(x, y)];
+        while let Some((x, y)) = stack.pop() {
+            if self.get_pixel(x, y) != target {
+                continue;
+            }
+            let mut left = x;
+            while left > 0 && self.get_pixel(left - 1, y) == target {
+                left -= 1;
+            }
+            let mut right = x;
+            while right + 1 < self.width && self.get_pixel(right + 1, y) == target {
+                right += 1;
+            }
+            for x in left..=right {
+                self.set_pixel(x, y, colour);
+            }
+
+            let rows = [y.checked_sub(1), Some(y + 1).filter(|y| *y < self.height)];
+            for row in rows.iter().flatten() {
+                let mut in_run = false;
+                for x in left..=right {
+                    let matches = self.get_pixel(x, *row) == target;
+                    if matches && !in_run {
+                        stack.push((x, *row));
+                    }
+                    in_run = matches;
+                }
+            }
+        }
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * self.width + x) * 4) as usize;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.pixels[i..i + 4]);
+        pixel
+    }
+
+    fn set_pixel(&mut self, x: u32, y: u32, colour: [u8; 4]) {
+        let i = ((y * self.width + x) * 4) as usize;
+        self.pixels[i..i + 4].copy_from_slice(&colour);
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut output = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut output, self.width, self.height);
+            encoder.set_color(png::ColorType::RGBA);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.write_header()?.write_image_data(&self.pixels)?;
+        }
+        Ok(output)
+    }
+}
+
+fn palette_colour(index: usize) -> [u8; 4] {
+    let (r, g, b) = PALETTE[index];
+    [r, g, b, 255]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+
+    #[test]
+    fn flood_fill_covers_a_big_empty_canvas() {
+        let mut bitmap = Bitmap::new(2000, 2000);
+        bitmap.flood_fill(1000, 1000, RED);
+        assert!(bitmap.pixels.chunks(4).all(|pixel| pixel == RED));
+    }
+
+    /// A bitmap with the outline of a box from 20 to 80, with a gap in its bottom
+    /// edge if `gap` is set
+    fn boxed(gap: bool) -> Bitmap {
+        let mut bitmap = Bitmap::new(100, 100);
+        let mut edges = vec![
+            (20, 20, 80, 20, 1),
+            (80, 20, 80, 80, 1),
+            (20, 80, 20, 20, 1),
+        ];
+        if gap {
+            edges.extend(&[(80, 80, 60, 80, 1), (40, 80, 20, 80, 1)]);
+        } else {
+            edges.push((80, 80, 20, 80, 1));
+        }
+        for edge in edges {
+            bitmap.draw_segment(edge, palette_colour(0));
+        }
+        bitmap
+    }
+
+    #[test]
+    fn flood_fill_stops_at_lines() {
+        let mut bitmap = boxed(false);
+        bitmap.flood_fill(50, 50, RED);
+        assert_eq!(bitmap.get_pixel(50, 50), RED);
+        assert_eq!(bitmap.get_pixel(20, 50), palette_colour(0));
+        assert_eq!(bitmap.get_pixel(5, 5), TRANSPARENT);
+        assert_eq!(bitmap.get_pixel(50, 95), TRANSPARENT);
+    }
+
+    #[test]
+    fn flood_fill_goes_through_gaps() {
+        let mut bitmap = boxed(true);
+        bitmap.flood_fill(50, 50, RED);
+        assert_eq!(bitmap.get_pixel(50, 95), RED);
+        assert_eq!(bitmap.get_pixel(5, 5), RED);
+        assert_eq!(bitmap.get_pixel(20, 50), palette_colour(0));
+    }
+}
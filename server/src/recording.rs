@@ -0,0 +1,293 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use actix::prelude::*;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::json_lines::JsonLines;
+use crate::server::*;
+use crate::session::format_event;
+
+/// One line of a recording
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Entry {
+    /// The first line, with the seed the game server's RNG started from and the
+    /// config it was running with
+    Start { seed: u64, config: Box<Config> },
+    /// A session connected and was given the id
+    Connect { session: usize },
+    /// A bot was added and given the id
+    Bot { session: usize },
+    Message {
+        session: usize,
+        room: Option<String>,
+        content: String,
+    },
+    Disconnect {
+        session: usize,
+        room: Option<String>,
+    },
+    /// A bot gave up drawing
+    GiveUp { session: usize, room: String },
+    /// The server was told to shut down
+    Shutdown,
+    /// The shutdown countdown finished
+    CloseSessions,
+    /// A room's timer went off
+    Timer { timer: Timer },
+    /// An event sent to a session, formatted without any timing so that it can be
+    /// compared when replayed
+    Event { session: usize, text: String },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Line {
+    /// Milliseconds since the recording started
+    time: u64,
+    #[serde(flatten)]
+    entry: Entry,
+}
+
+/// Records everything sent into the game server, and every event it sends to a
+/// session, as JSON lines so that the game can be replayed later
+pub struct Recorder {
+    file: JsonLines,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Creates the recording, overwriting the file if it exists
+    pub fn new<P: AsRef<Path>>(path: P, seed: u64, config: &Config) -> std::io::Result<Recorder> {
+        let recorder = Recorder {
+            file: JsonLines::new("recording", File::create(path)?),
+            started: Instant::now(),
+        };
+        recorder.record(Entry::Start {
+            seed,
+            config: Box::new(config.clone()),
+        });
+        Ok(recorder)
+    }
+
+    pub fn record(&self, entry: Entry) {
+        self.file.write(&Line {
+            time: self.started.elapsed().as_millis() as u64,
+            entry,
+        });
+    }
+
+    /// Waits for everything recorded so far to be written
+    pub fn close(&self) {
+        self.file.close();
+    }
+}
+
+/// Sits between the game server and a session, recording every event sent to it
+pub struct RecordingProxy {
+    pub session_id: usize,
+    pub recipient: Recipient<Event>,
+    pub recorder: Arc<Recorder>,
+}
+
+impl Actor for RecordingProxy {
+    type Context = Context<Self>;
+}
+
+impl Handler<Event> for RecordingProxy {
+    type Result = ();
+
+    /// Events are recorded even once the session has gone, since the game server
+    /// sends them the same way when replaying
+    fn handle(&mut self, event: Event, _: &mut Context<Self>) {
+        self.recorder.record(Entry::Event {
+            session: self.session_id,
+            text: format_event(event.clone().without_timing()),
+        });
+        let _ = self.recipient.do_send(event);
+    }
+}
+
+/// The events each session was sent while replaying, formatted without timing
+type Sent = Arc<Mutex<HashMap<usize, Vec<String>>>>;
+
+/// Stands in for a session while replaying, keeping every event it's sent
+struct Collector {
+    session_id: usize,
+    sent: Sent,
+}
+
+impl Actor for Collector {
+    type Context = Context<Self>;
+}
+
+/// Answered once a collector has kept every event sent to it before
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Flush;
+
+impl Handler<Flush> for Collector {
+    type Result = ();
+
+    fn handle(&mut self, _: Flush, _: &mut Context<Self>) {}
+}
+
+impl Handler<Event> for Collector {
+    type Result = ();
+
+    fn handle(&mut self, event: Event, _: &mut Context<Self>) {
+        let mut sent = self.sent.lock().expect("sent events lock was poisoned");
+        sent.entry(self.session_id)
+            .or_default()
+            .push(format_event(event.without_timing()));
+    }
+}
+
+/// Replays a recording against a fresh game server started with the recorded seed,
+/// config and session ids, then checks that each session was sent the same events. Returns
+/// how the events sent differed from the recording, which is empty if they all matched.
+///
+/// Everything is sent in as fast as the game server handles it, with timers going
+/// off where the recording says they did, so replays don't depend on how long
+/// anything takes.
+pub async fn replay<P: AsRef<Path>>(
+    path: P,
+    game_server: impl FnOnce(u64, Config, VecDeque<usize>) -> Addr<GameServer>,
+) -> std::io::Result<Vec<String>> {
+    let lines = BufReader::new(File::open(path)?)
+        .lines()
+        .map(|line| {
+            serde_json::from_str(&line?)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        })
+        .collect::<std::io::Result<Vec<Line>>>()?;
+    // Ids are random each time, so the game server is given the recorded ones
+    let ids = lines
+        .iter()
+        .filter_map(|line| match line.entry {
+            Entry::Connect { session } | Entry::Bot { session } => Some(session),
+            _ => None,
+        })
+        .collect();
+    let mut lines = lines.into_iter();
+    let (seed, config) = match lines.next() {
+        Some(Line {
+            entry: Entry::Start { seed, config },
+            ..
+        }) => (seed, *config),
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "the recording didn't start with the seed",
+            ))
+        }
+    };
+    info!("Replaying recording with seed {}", seed);
+    let game_server = game_server(seed, config, ids);
+    let sent = Sent::default();
+    let mut collectors = Vec::new();
+    let mut expected: HashMap<usize, Vec<String>> = HashMap::new();
+    // Waits for the game server to handle each message before sending the next
+    let mailbox = |e: MailboxError| {
+        let kind = match e {
+            MailboxError::Closed => std::io::ErrorKind::BrokenPipe,
+            MailboxError::Timeout => std::io::ErrorKind::TimedOut,
+        };
+        std::io::Error::new(kind, e)
+    };
+    for Line { entry, .. } in lines {
+        match entry {
+            Entry::Start { .. } => warn!("Recording had more than one start, ignoring it"),
+            Entry::Connect { session } => {
+                let collector = Collector {
+                    session_id: session,
+                    sent: Arc::clone(&sent),
+                }
+                .start();
+                let recipient = collector.clone().recipient();
+                collectors.push(collector);
+                let id = game_server
+                    .send(ConnectMessage { recipient })
+                    .await
+                    .map_err(mailbox)?;
+                if id != session {
+                    return Ok(vec![format!(
+                        "Session {} was given id {} when replayed, so the replay can't continue",
+                        session, id
+                    )]);
+                }
+            }
+            // The bot's id is already queued up, and the message adding it follows
+            Entry::Bot { .. } => {}
+            Entry::Message {
+                session,
+                room,
+                content,
+            } => game_server
+                .send(ClientMessage {
+                    session_id: session,
+                    content,
+                    room,
+                })
+                .await
+                .map_err(mailbox)?,
+            Entry::Disconnect { session, room } => game_server
+                .send(DisconnectMessage {
+                    session_id: session,
+                    room,
+                })
+                .await
+                .map_err(mailbox)?,
+            Entry::GiveUp { session, room } => game_server
+                .send(GiveUp {
+                    session_id: session,
+                    room,
+                })
+                .await
+                .map_err(mailbox)?,
+            Entry::Shutdown => {
+                game_server.send(Shutdown).await.map_err(mailbox)?;
+            }
+            Entry::CloseSessions => game_server.send(CloseSessions).await.map_err(mailbox)?,
+            Entry::Timer { timer } => game_server.send(timer).await.map_err(mailbox)?,
+            Entry::Event { session, text } => expected.entry(session).or_default().push(text),
+        }
+    }
+    // Everything the game server sent is in the collectors' mailboxes by now
+    for collector in collectors {
+        collector.send(Flush).await.map_err(mailbox)?;
+    }
+
+    let sent = sent.lock().expect("sent events lock was poisoned");
+    let mut differences = Vec::new();
+    let mut sessions: Vec<_> = expected.keys().collect();
+    sessions.sort_unstable();
+    for session in sessions {
+        let expected = &expected[session];
+        let replayed = sent.get(session).map(Vec::as_slice).unwrap_or_default();
+        let mismatch = expected
+            .iter()
+            .zip(replayed)
+            .position(|(expected, replayed)| expected != replayed);
+        match mismatch {
+            Some(i) => differences.push(format!(
+                "Session {} event {} differed\n  recorded: {}\n  replayed: {}",
+                session, i, expected[i], replayed[i]
+            )),
+            None if expected.len() != replayed.len() => differences.push(format!(
+                "Session {} was sent {} events when recorded but {} when replayed",
+                session,
+                expected.len(),
+                replayed.len()
+            )),
+            None => info!("Session {} matched {} events", session, expected.len()),
+        }
+    }
+    Ok(differences)
+}
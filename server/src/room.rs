@@ -1,14 +1,24 @@
-use std::collections::{HashMap, VecDeque};
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use actix::prelude::*;
-use log::{error, trace, warn};
-use rand::prelude::*;
+use log::{error, info, trace, warn};
+use rand::{prelude::*, rngs::StdRng};
 
-use crate::{server::GameServer, word_pack::WordPack, Event};
-
-const ROUND_LIMIT: Duration = Duration::from_secs(120);
+use crate::{
+    config::{Config, RoomConfig},
+    draw_history::{CanvasSettings, DrawHistory, Stroke, Tool, PALETTE},
+    event_log::{EventLog, GameEvent},
+    game_mode::{Classic, GameMode, Guess, Next, SoloGame, GAME_MODES},
+    metrics::{Metrics, StateName},
+    raster::Bitmap,
+    server::{GameServer, RecordDrawing, Timer},
+    snapshot::{PlayerSnapshot, RoomSnapshot},
+    word_filter::WordFilter,
+    word_pack::{WordChooser, WordPack},
+    Event,
+};
 
 struct LobbyState {
     pub host: usize,
@@ -16,8 +26,10 @@ struct LobbyState {
 
 struct RoundState {
     pub word: (usize, usize),
-    pub leader: usize,
+    /// Everyone drawing this round, the first was picked first
+    pub leaders: Vec<usize>,
     pub timeout: Option<u128>,
+    pub started: Instant,
 }
 
 struct WinnerState {
@@ -33,11 +45,86 @@ struct Settings {
     pub allow_clear: bool,
     pub enabled_word_packs: Vec<usize>,
     pub custom_words: Option<WordPack>,
+    /// How many rounds are played before the game ends, if there is a limit
+    pub round_limit: Option<usize>,
+    pub canvas: CanvasSettings,
+    /// The id of the game mode, see `GAME_MODES`
+    pub mode: usize,
+    /// Options for the game mode, which the mode parses itself
+    pub mode_options: String,
+    /// Once a round's draw history has this many segments, finished strokes are
+    /// simplified
+    pub simplify_draw_history: usize,
+    /// The most segments a round's draw history can hold
+    pub max_draw_history: usize,
 }
 impl Settings {
-    fn parse_from_lines(lines: Vec<String>, max_wordpack_id: usize) -> Option<Settings> {
-        if let [wordpacks, time_limit, canvas_clearing, custom_words] = &*lines {
-            let wordpacks = if wordpacks.len() > 0 {
+    fn from_config(config: &RoomConfig) -> Settings {
+        Settings {
+            round_timer: config.round_timer,
+            allow_clear: config.allow_clear,
+            round_limit: config.round_limit(),
+            canvas: config.canvas(),
+            mode: config.mode,
+            simplify_draw_history: config.simplify_draw_history,
+            max_draw_history: config.max_draw_history,
+            ..Settings::default()
+        }
+    }
+
+    /// Parses the settings a host started a game with, anything optional they left
+    /// out comes from the server's default room settings. The draw history can't be
+    /// made bigger than the server's limits.
+    fn parse_from_lines(
+        lines: Vec<String>,
+        max_wordpack_id: usize,
+        config: &Config,
+    ) -> Option<Settings> {
+        let defaults = &config.room;
+        if let [wordpacks, time_limit, canvas_clearing, custom_words, rest @ ..] = &*lines {
+            // The round limit, canvas settings, game mode and its options and the
+            // draw history sizes are optional, a round limit of 0 means there isn't one
+            let mut rest = rest.iter().map(|line| line.trim());
+            let round_limit = match rest.next() {
+                Some(round_limit) => Some(round_limit.parse::<usize>().ok()?).filter(|x| *x > 0),
+                None => defaults.round_limit(),
+            };
+            let canvas = match (rest.next(), rest.next(), rest.next()) {
+                (None, None, None) => defaults.canvas(),
+                (Some(width), Some(height), Some(max_pen_size)) => CanvasSettings::new(
+                    width.parse().ok()?,
+                    height.parse().ok()?,
+                    max_pen_size.parse().ok()?,
+                )?,
+                _ => return None,
+            };
+            let mode = match rest.next() {
+                Some(mode) => {
+                    Some(mode.parse::<usize>().ok()?).filter(|x| *x < GAME_MODES.len())?
+                }
+                None => defaults.mode,
+            };
+            let mode_options = rest.next().unwrap_or("").to_string();
+            let (max_draw_history, simplify_draw_history) = match (rest.next(), rest.next()) {
+                (None, None) => (defaults.max_draw_history, defaults.simplify_draw_history),
+                (Some(max), Some(simplify)) => {
+                    let max = max.parse::<usize>().ok()?;
+                    let simplify = simplify.parse::<usize>().ok()?;
+                    if max == 0
+                        || max > config.limits.max_draw_history
+                        || simplify > max
+                        || simplify > config.limits.simplify_draw_history
+                    {
+                        return None;
+                    }
+                    (max, simplify)
+                }
+                _ => return None,
+            };
+            if rest.next().is_some() {
+                return None;
+            }
+            let wordpacks = if !wordpacks.is_empty() {
                 wordpacks
                     .split(',')
                     .map(|x| {
@@ -53,11 +140,15 @@ impl Settings {
             } else {
                 vec![]
             };
+            // Words are sent on a line of their own, so they can't have line breaks
+            if custom_words.chars().any(char::is_control) {
+                return None;
+            }
             let custom_words: Option<WordPack> = custom_words
                 .split('|')
                 .map(|x| {
                     let mut iter = x.split(',').filter_map(|x| {
-                        if x.trim().len() > 0 {
+                        if !x.trim().is_empty() {
                             Some(x.trim().to_string())
                         } else {
                             None
@@ -74,37 +165,107 @@ impl Settings {
                 round_timer: time_limit == "T",
                 allow_clear: canvas_clearing == "T",
                 custom_words,
+                round_limit,
+                canvas,
+                mode,
+                mode_options,
+                simplify_draw_history,
+                max_draw_history,
             });
         }
         None
     }
 }
 
+/// Everything needed to show a finished round in the gallery
+#[derive(Clone)]
+pub struct RoundRecord {
+    pub canvas: CanvasSettings,
+    pub word: String,
+    pub leader: usize,
+    pub winner: Option<usize>,
+    pub strokes: Vec<Stroke>,
+    /// The drawing as a PNG once it's been asked for, shared by every copy of the
+    /// record so that it's only ever rasterised once
+    pub png: Arc<Mutex<Option<Arc<Vec<u8>>>>>,
+}
+
+impl RoundRecord {
+    /// The drawing as a PNG, rasterising it the first time it's asked for. This
+    /// can take a while for big drawings, so it shouldn't be called from an async
+    /// worker.
+    pub fn png(&self) -> Result<Arc<Vec<u8>>, png::EncodingError> {
+        // Held while rasterising so that requests at the same time wait for it
+        let mut png = self.png.lock().expect("drawing PNG lock was poisoned");
+        if let Some(png) = &*png {
+            return Ok(Arc::clone(png));
+        }
+        let encoded = Arc::new(Bitmap::rasterise(self.canvas, &self.strokes).to_png()?);
+        *png = Some(Arc::clone(&encoded));
+        Ok(encoded)
+    }
+}
+
+/// An occupant's connection, username and points
+pub type Occupant = (Recipient<Event>, String, usize);
+
 enum RoomState {
     Lobby(LobbyState),
     Round(RoundState),
     Winner(WinnerState),
+    /// The game mode is running a game where everyone plays at once, see `SoloGame`
+    Solo,
+}
+
+/// Which canvas a draw command goes to
+#[derive(Clone, Copy)]
+enum CanvasId {
+    /// The round's canvas that everyone sees
+    Shared,
+    /// A player's own canvas that only they see while they draw on it
+    Personal(usize),
 }
 
 pub struct Room {
     state: RoomState,
     key: String,
-    occupants: HashMap<usize, (Recipient<Event>, String, usize)>,
+    occupants: HashMap<usize, Occupant>,
+    /// The occupants played by the server
+    bots: BTreeSet<usize>,
     word_packs: Arc<Vec<WordPack>>,
-    num_words: usize,
+    word_filter: Arc<WordFilter>,
+    metrics: Arc<Metrics>,
+    event_log: Arc<EventLog>,
+    config: Arc<Config>,
+    /// Seeds the word chooser of each game
+    rng: StdRng,
+    words: WordChooser,
     settings: Settings,
-    rng: ThreadRng,
+    mode: Box<dyn GameMode>,
     queue: VecDeque<usize>,
-    excluded_words: VecDeque<usize>,
-    max_excluded_words: usize,
-    draw_history: Vec<(u32, u32, u32, u32, u32)>,
+    draw_history: DrawHistory,
+    /// The canvas of each player drawing at the same time as everyone else
+    canvases: HashMap<usize, DrawHistory>,
+    /// When the players were given their canvases
+    canvases_started: Instant,
+    /// Every finished round of the current (or most recent) game, oldest first. In
+    /// games where everyone draws each drawing gets its own record. Shared with the
+    /// gallery events sent out, so that sending the gallery doesn't copy it.
+    rounds: Arc<Vec<Arc<RoundRecord>>>,
+    rounds_played: usize,
     round_id: usize,
 }
 
 impl Room {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         key: String,
         word_packs: Arc<Vec<WordPack>>,
+        word_filter: Arc<WordFilter>,
+        metrics: Arc<Metrics>,
+        event_log: Arc<EventLog>,
+        config: Arc<Config>,
+        rng: StdRng,
         session_id: usize,
         recipient: Recipient<Event>,
         username: String,
@@ -117,32 +278,43 @@ impl Room {
             state: RoomState::Lobby(LobbyState { host: session_id }),
             key: key.clone(),
             occupants,
-            max_excluded_words: 0,
+            bots: BTreeSet::new(),
             word_packs,
-            num_words: 0,
-            settings: Settings::default(),
-            rng: ThreadRng::default(),
+            word_filter,
+            metrics,
+            event_log,
+            settings: Settings::from_config(&config.room),
+            config,
+            rng,
+            words: WordChooser::default(),
+            mode: Box::new(Classic),
             queue,
-            excluded_words: VecDeque::new(),
-            draw_history: Vec::new(),
+            draw_history: DrawHistory::default(),
+            canvases: HashMap::new(),
+            canvases_started: Instant::now(),
+            rounds: Arc::default(),
+            rounds_played: 0,
             round_id: 0,
         };
+        room.event_log.record(
+            &key,
+            GameEvent::RoomCreated {
+                session: session_id,
+                username: &username,
+            },
+        );
         room.direct_message(
             &recipient,
             Event::EnterRoom(key, vec![(session_id, username)]),
         );
+        room.direct_message(&recipient, Event::CanvasSettings(room.settings.canvas));
         room.direct_message(&recipient, Event::EnterLobby(session_id));
         room.send_settings_data(&recipient);
         room
     }
 
     fn direct_message(&self, recipient: &Recipient<Event>, event: Event) {
-        if recipient.do_send(event).is_err() {
-            // TODO: try to fix sending leave message when socket disconnects
-            // so that this is rare, hence upgrading from
-            // trace (common behaviour) to warn (uncommon - indicates bug)
-            trace!("Tried to send message to disconnected socket");
-        }
+        send_event(&self.metrics, recipient, event);
     }
 
     fn broadcast_event(&self, event: Event) {
@@ -158,45 +330,17 @@ impl Room {
             .enumerate()
             .map(|(i, pack)| (i, pack.get_name().clone(), pack.get_description().clone()))
             .collect();
-        self.direct_message(&recipient, Event::SettingsData(data));
-    }
-
-    fn choose_new_word(&mut self) -> (usize, usize) {
-        loop {
-            let word_index = self.rng.gen_range(0, self.num_words);
-            if !self.excluded_words.contains(&word_index) {
-                if self.excluded_words.len() >= self.max_excluded_words {
-                    self.excluded_words.pop_front();
-                }
-                if self.excluded_words.len() < self.max_excluded_words {
-                    self.excluded_words.push_back(word_index);
-                }
-                let mut acc = 0;
-                for i in &self.settings.enabled_word_packs {
-                    if self.word_packs[*i].list_len() + acc > word_index {
-                        return (*i, word_index - acc);
-                    }
-                    acc += self.word_packs[*i].list_len();
-                }
-                assert!(
-                    self.settings.custom_words.is_some(),
-                    "word_index is out of bounds so custom_words must exist"
-                );
-                assert!(
-                    self.settings.custom_words.as_ref().unwrap().list_len() + acc > word_index,
-                    "word_index was out of bounds of all wordpacks including custom"
-                );
-                return (self.word_packs.len(), word_index - acc);
-            }
-        }
+        self.direct_message(recipient, Event::SettingsData(data));
+        let modes = GAME_MODES
+            .iter()
+            .enumerate()
+            .map(|(i, mode)| (i, mode.name, mode.description))
+            .collect();
+        self.direct_message(recipient, Event::GameModes(modes));
     }
 
     fn get_wordpack(&self, pack: usize) -> &WordPack {
-        if pack == self.word_packs.len() {
-            self.settings.custom_words.as_ref().unwrap()
-        } else {
-            &self.word_packs[pack]
-        }
+        word_pack(&self.word_packs, &self.settings, pack)
     }
 
     fn get_word(&self, word: (usize, usize)) -> &String {
@@ -210,20 +354,23 @@ impl Room {
     pub fn start(&mut self, session_id: usize, lines: Vec<String>, ctx: &mut Context<GameServer>) {
         if let RoomState::Lobby(LobbyState { host }) = self.state {
             if session_id == host {
-                if let Some(settings) = Settings::parse_from_lines(lines, self.word_packs.len()) {
-                    self.num_words = self
-                        .word_packs
+                if let Some(settings) =
+                    Settings::parse_from_lines(lines, self.word_packs.len(), &self.config)
+                {
+                    // Custom words are treated as the word pack after all the others
+                    let pack_sizes = settings
+                        .enabled_word_packs
                         .iter()
-                        .enumerate()
-                        .filter(|(i, _)| settings.enabled_word_packs.contains(&i))
-                        .map(|(_, x)| x.list_len())
-                        .sum::<usize>()
-                        + settings
-                            .custom_words
-                            .as_ref()
-                            .map(|x| x.list_len())
-                            .unwrap_or(0);
-                    if self.num_words == 0 {
+                        .map(|i| (*i, self.word_packs[*i].list_len()))
+                        .chain(
+                            settings
+                                .custom_words
+                                .as_ref()
+                                .map(|x| (self.word_packs.len(), x.list_len())),
+                        )
+                        .collect();
+                    let words = WordChooser::new(pack_sizes, self.rng.gen());
+                    if words.num_words() == 0 {
                         warn!(
                             "tried to start game with no word packs in room {}",
                             self.key
@@ -233,11 +380,66 @@ impl Room {
                     trace!(
                         "room {} started with {} words, settings: {:?}",
                         self.key,
-                        self.num_words,
+                        words.num_words(),
                         settings
                     );
-                    self.max_excluded_words = std::cmp::min(100, self.num_words / 10);
+                    let mode = match GAME_MODES[settings.mode].create(&settings.mode_options) {
+                        Some(mode) => mode,
+                        None => {
+                            warn!(
+                                "session id {} sent invalid game mode options in room {}: {}",
+                                session_id, self.key, settings.mode_options
+                            );
+                            return;
+                        }
+                    };
+                    let players: Vec<_> = self
+                        .queue
+                        .iter()
+                        .copied()
+                        .filter(|id| self.occupants.contains_key(id))
+                        .collect();
+                    if players.len() < mode.min_players() {
+                        warn!(
+                            "tried to start game with {} players when {} are needed in room {}",
+                            players.len(),
+                            mode.min_players(),
+                            self.key
+                        );
+                        return;
+                    }
+                    self.words = words;
+                    self.mode = mode;
                     self.settings = settings;
+                    self.rounds = Arc::default();
+                    self.rounds_played = 0;
+                    self.event_log.record(
+                        &self.key,
+                        GameEvent::Settings {
+                            session: session_id,
+                            mode: GAME_MODES[self.settings.mode].name,
+                            mode_options: &self.settings.mode_options,
+                            word_packs: &self.settings.enabled_word_packs,
+                            custom_words: self
+                                .settings
+                                .custom_words
+                                .as_ref()
+                                .map_or(0, |words| words.list_len()),
+                            round_timer: self.settings.round_timer,
+                            allow_clear: self.settings.allow_clear,
+                            round_limit: self.settings.round_limit,
+                            width: self.settings.canvas.width,
+                            height: self.settings.canvas.height,
+                            max_pen_size: self.settings.canvas.max_pen_size,
+                            max_draw_history: self.settings.max_draw_history,
+                            simplify_draw_history: self.settings.simplify_draw_history,
+                            players: &players,
+                        },
+                    );
+                    self.mode.start_game(&players);
+                    // Always sent so that a scoreboard from an earlier game is cleared
+                    self.broadcast_event(Event::Scoreboard(self.mode.scoreboard()));
+                    self.broadcast_event(Event::CanvasSettings(self.settings.canvas));
                     self.new_round(ctx);
                 } else {
                     warn!(
@@ -259,8 +461,14 @@ impl Room {
         }
     }
 
-    pub fn join(&mut self, session_id: usize, recipient: Recipient<Event>, username: String) {
-        if self.occupants.get(&session_id).is_some() {
+    pub fn join(
+        &mut self,
+        session_id: usize,
+        recipient: Recipient<Event>,
+        username: String,
+        ctx: &mut Context<GameServer>,
+    ) {
+        if self.occupants.contains_key(&session_id) {
             warn!(
                 "User {} ({}) is already in room {}",
                 username, session_id, self.key
@@ -275,6 +483,13 @@ impl Room {
         }
 
         trace!("{} ({}) joining room {}", username, session_id, self.key);
+        self.event_log.record(
+            &self.key,
+            GameEvent::Joined {
+                session: session_id,
+                username: &username,
+            },
+        );
         self.broadcast_event(Event::UserJoin(session_id, username.clone()));
         self.occupants
             .insert(session_id, (recipient.clone(), username, 0));
@@ -282,14 +497,24 @@ impl Room {
             &recipient,
             Event::EnterRoom(self.key.to_string(), self.get_user_list()),
         );
+        self.direct_message(&recipient, Event::CanvasSettings(self.settings.canvas));
+        if !self.bots.is_empty() {
+            self.direct_message(&recipient, Event::Bots(self.bots.iter().copied().collect()));
+        }
+        if !matches!(self.state, RoomState::Lobby(_)) {
+            self.mode.player_joined(session_id);
+            self.send_scoreboard();
+        }
         match self.state {
             RoomState::Lobby(LobbyState { host }) => {
                 self.direct_message(&recipient, Event::EnterLobby(host));
             }
             RoomState::Round(RoundState {
-                leader, timeout, ..
+                ref leaders,
+                timeout,
+                ..
             }) => {
-                self.direct_message(&recipient, Event::NewRound(leader, timeout));
+                self.direct_message(&recipient, Event::NewRound(leaders.clone(), timeout));
                 self.send_draw_history(session_id, &recipient);
             }
             RoomState::Winner(WinnerState {
@@ -309,44 +534,78 @@ impl Room {
                 );
                 self.send_draw_history(session_id, &recipient);
             }
+            RoomState::Solo => {
+                self.with_solo_game(ctx, |game, room| game.joined(session_id, room));
+            }
         }
         self.queue.push_back(session_id);
     }
 
+    /// Sends everyone the team scores if the game mode has teams
+    fn send_scoreboard(&self) {
+        let scoreboard = self.mode.scoreboard();
+        if !scoreboard.is_empty() {
+            self.broadcast_event(Event::Scoreboard(scoreboard));
+        }
+    }
+
+    /// Sends everything currently on the canvas as a single event so that players
+    /// joining mid-round don't get flooded with draw events
     fn send_draw_history(&self, session_id: usize, recipient: &Recipient<Event>) {
         trace!(
             "Sending draw history of {} commands to {}",
-            self.draw_history.len(),
+            self.draw_history.segment_count(),
             session_id
         );
-        for (x1, x2, y1, y2, pen_size) in &self.draw_history {
-            self.direct_message(recipient, Event::Draw(*x1, *x2, *y1, *y2, *pen_size));
-        }
+        self.direct_message(
+            recipient,
+            Event::DrawSnapshot(self.draw_history.strokes().to_vec()),
+        );
     }
 
     pub fn leave(&mut self, session_id: usize, ctx: &mut Context<GameServer>) -> bool {
         trace!("{} leaving room {}", session_id, self.key);
         if let Some((recipient, _, _)) = self.occupants.remove(&session_id) {
+            self.event_log.record(
+                &self.key,
+                GameEvent::Left {
+                    session: session_id,
+                },
+            );
             self.direct_message(&recipient, Event::LeaveRoom);
             self.broadcast_event(Event::UserGone(session_id));
-            if self.occupants.is_empty() {
+            if self.bots.remove(&session_id) {
+                self.broadcast_event(Event::Bots(self.bots.iter().copied().collect()));
+            }
+            if self.occupants.keys().all(|id| self.bots.contains(id)) {
+                for bot in &self.bots {
+                    let (recipient, _, _) = &self.occupants[bot];
+                    self.event_log
+                        .record(&self.key, GameEvent::Left { session: *bot });
+                    self.direct_message(recipient, Event::LeaveRoom);
+                }
                 return true;
             }
+            if !matches!(self.state, RoomState::Lobby(_)) {
+                self.mode.player_left(session_id);
+                self.send_scoreboard();
+            }
             match self.state {
-                RoomState::Lobby(LobbyState { host }) => {
-                    if host == session_id {
-                        let new_leader = self
-                            .queue
-                            .iter()
-                            .find(|id| self.occupants.get(id).is_some())
-                            .expect("user was in occupants but not queue");
-                        self.state = RoomState::Lobby(LobbyState { host: *new_leader });
-                        self.broadcast_event(Event::EnterLobby(*new_leader));
-                        self.send_settings_data(&self.occupants.get(new_leader).unwrap().0);
-                    }
+                RoomState::Lobby(LobbyState { host }) if host == session_id => {
+                    let new_leader = self
+                        .queue
+                        .iter()
+                        .find(|id| self.occupants.contains_key(id) && !self.bots.contains(id))
+                        .expect("user was in occupants but not queue");
+                    self.state = RoomState::Lobby(LobbyState { host: *new_leader });
+                    self.broadcast_event(Event::EnterLobby(*new_leader));
+                    self.send_settings_data(&self.occupants.get(new_leader).unwrap().0);
                 }
-                RoomState::Round(RoundState { leader, .. }) => {
-                    if leader == session_id {
+                RoomState::Round(RoundState {
+                    ref mut leaders, ..
+                }) if leaders.contains(&session_id) => {
+                    leaders.retain(|id| *id != session_id);
+                    if leaders.is_empty() {
                         trace!(
                             "Current leader ({}) left room so new round in room {}",
                             session_id,
@@ -355,6 +614,16 @@ impl Room {
                         self.new_round(ctx);
                     }
                 }
+                RoomState::Solo => {
+                    self.canvases.remove(&session_id);
+                    let can_go_on =
+                        self.with_solo_game(ctx, |game, room| !game.left(session_id, room));
+                    if can_go_on == Some(false) {
+                        self.finish_step(ctx);
+                    } else {
+                        self.progress(ctx);
+                    }
+                }
                 _ => {}
             }
         } else {
@@ -373,92 +642,359 @@ impl Room {
         alternate: Option<usize>,
         ctx: &mut Context<GameServer>,
     ) {
-        if let RoomState::Round(RoundState { word, leader, .. }) = self.state {
+        if let RoomState::Round(RoundState {
+            word, ref leaders, ..
+        }) = self.state
+        {
+            let leaders = leaders.clone();
+            self.draw_history.simplify_all();
+            let record = Arc::new(RoundRecord {
+                canvas: self.settings.canvas,
+                word: self.get_word(word).clone(),
+                // Any other leaders can be found from who drew the strokes
+                leader: leaders[0],
+                winner,
+                strokes: self.draw_history.strokes().to_vec(),
+                png: Arc::default(),
+            });
+            // Bots learn to draw words from drawings that people managed to guess
+            if winner.is_some()
+                && !record.strokes.is_empty()
+                && leaders.iter().all(|id| !self.bots.contains(id))
+            {
+                ctx.notify(RecordDrawing {
+                    record: Arc::clone(&record),
+                });
+            }
+            Arc::make_mut(&mut self.rounds).push(record);
+            self.rounds_played += 1;
+            self.metrics.round_finished();
+            if let Some(winner) = winner {
+                self.metrics.round_won();
+                self.event_log.record(
+                    &self.key,
+                    GameEvent::Win {
+                        round: self.round_id,
+                        winner,
+                        leaders: &leaders,
+                        word: self.get_word(word),
+                        points,
+                    },
+                );
+            }
             self.state = RoomState::Winner(WinnerState {
                 winner,
                 points,
                 word,
                 alternate,
             });
-            self.queue.push_back(leader);
+            self.mode.finish_round(&leaders, &mut self.queue);
             self.broadcast_event(Event::Winner(
                 winner,
                 points,
                 self.get_word(word).clone(),
                 alternate.map(|x| self.get_alternate(word, x).clone()),
             ));
-            let key = self.key.clone();
-            ctx.run_later(Duration::new(5, 0), move |act, ctx| {
-                act.new_round(key, ctx);
-            });
+            self.send_scoreboard();
+            let timer = Timer::NewRound {
+                room: self.key.clone(),
+            };
+            GameServer::start_timer(self.mode.intermission(&self.config.timers), timer, ctx);
         } else {
             error!("end_round called with invalid state in room {}", self.key);
         }
     }
 
     pub fn new_round(&mut self, ctx: &mut Context<GameServer>) {
-        self.round_id += 1;
-        let word = self.choose_new_word();
+        if self
+            .mode
+            .is_game_over(self.rounds_played, self.settings.round_limit)
+        {
+            self.end_game();
+            return;
+        }
+        if self.rounds.len() >= self.config.limits.max_gallery_rounds {
+            info!(
+                "Room {} has kept {} rounds for its gallery, ending the game",
+                self.key,
+                self.rounds.len()
+            );
+            self.end_game();
+            return;
+        }
+        match self.with_solo_game(ctx, |game, room| game.new_round(room)) {
+            Some(Next::EndGame) => {
+                self.end_game();
+                return;
+            }
+            Some(_) => {
+                self.state = RoomState::Solo;
+                return;
+            }
+            None => {}
+        }
 
+        self.round_id += 1;
+        let word = self.mode.choose_word(&mut self.words);
         self.draw_history.clear();
-        while let Some(new_leader) = self.queue.pop_front() {
-            if self.occupants.get(&new_leader).is_some() {
-                use std::time::SystemTime;
-
-                let timestamp = if self.settings.round_timer {
-                    let now = SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .expect("went backwards in time");
-                    Some((now + ROUND_LIMIT).as_millis())
-                } else {
-                    None
-                };
-                self.state = RoomState::Round(RoundState {
-                    word,
-                    leader: new_leader,
-                    timeout: timestamp,
-                });
-                for (session_id, (recipient, _, _)) in self.occupants.iter() {
-                    if *session_id != new_leader {
-                        self.direct_message(recipient, Event::NewRound(new_leader, timestamp));
-                    } else {
-                        self.direct_message(
-                            recipient,
-                            Event::NewLeader(
-                                self.settings.allow_clear,
-                                self.get_word(word).clone(),
-                                timestamp,
-                            ),
-                        );
-                    }
-                }
+        let leaders = self.mode.choose_leaders(&mut self.queue, &self.occupants);
+        if leaders.is_empty() {
+            info!(
+                "Room {} had no possible leader for new round, ending the game",
+                self.key
+            );
+            self.end_game();
+            return;
+        }
+        let timestamp = self.timeout_after(self.config.timers.drawing_limit());
+        for (session_id, (recipient, _, _)) in self.occupants.iter() {
+            if !leaders.contains(session_id) {
+                self.direct_message(recipient, Event::NewRound(leaders.clone(), timestamp));
+            } else {
+                let others = leaders.iter().copied().filter(|id| id != session_id);
+                self.direct_message(
+                    recipient,
+                    Event::NewLeader(
+                        self.settings.allow_clear,
+                        self.get_word(word).clone(),
+                        others.collect(),
+                        timestamp,
+                    ),
+                );
+            }
+        }
+        trace!(
+            "Room {} has new round with word {:?}, leaders {:?}",
+            self.key,
+            word,
+            leaders,
+        );
+        self.event_log.record(
+            &self.key,
+            GameEvent::RoundStart {
+                round: self.round_id,
+                leaders: &leaders,
+                word: self.get_word(word),
+            },
+        );
+        self.state = RoomState::Round(RoundState {
+            word,
+            leaders,
+            timeout: timestamp,
+            started: Instant::now(),
+        });
+        self.metrics.round_started();
 
-                if self.settings.round_timer {
-                    let round_id = self.round_id;
-                    let key = self.key.clone();
-                    ctx.run_later(ROUND_LIMIT, move |server, ctx| {
-                        server.round_timeout(&key, round_id, ctx);
-                    });
-                }
+        self.start_round_timer(self.config.timers.drawing_limit(), ctx);
+    }
 
-                trace!(
-                    "Room {} has new round with word {:?}, leader {}",
-                    self.key,
-                    word,
-                    new_leader,
-                );
-                return;
+    /// When something lasting `limit` from now times out, if the round timer is on
+    fn timeout_after(&self, limit: Duration) -> Option<u128> {
+        timeout_after(&self.settings, limit)
+    }
+
+    /// Times out the current round after `limit` if the round timer is on
+    fn start_round_timer(&self, limit: Duration, ctx: &mut Context<GameServer>) {
+        start_round_timer(&self.settings, &self.key, self.round_id, limit, ctx);
+    }
+
+    /// Runs `f` with the game mode's game if everyone is playing at once, giving it
+    /// everything in the room it can use
+    fn with_solo_game<R>(
+        &mut self,
+        ctx: &mut Context<GameServer>,
+        f: impl FnOnce(&mut dyn SoloGame, &mut RoomHandle) -> R,
+    ) -> Option<R> {
+        let game = self.mode.solo_game_mut()?;
+        let mut room = RoomHandle {
+            key: &self.key,
+            occupants: &mut self.occupants,
+            bots: &self.bots,
+            queue: &mut self.queue,
+            canvases: &mut self.canvases,
+            canvases_started: &mut self.canvases_started,
+            rounds: &mut self.rounds,
+            rounds_played: &mut self.rounds_played,
+            round_id: &mut self.round_id,
+            words: &mut self.words,
+            word_packs: &self.word_packs,
+            settings: &self.settings,
+            word_filter: &self.word_filter,
+            metrics: &self.metrics,
+            event_log: &self.event_log,
+            config: &self.config,
+            ctx,
+        };
+        Some(f(game, &mut room))
+    }
+
+    /// Carries on once a game where everyone plays at once has moved on
+    fn follow(&mut self, next: Next, ctx: &mut Context<GameServer>) {
+        match next {
+            Next::Continue => {}
+            Next::NextRound => {
+                // The round is over, so the timer of its last step can't time it out
+                self.round_id += 1;
+                self.send_scoreboard();
+                let timer = Timer::NewRound {
+                    room: self.key.clone(),
+                };
+                GameServer::start_timer(self.mode.intermission(&self.config.timers), timer, ctx);
             }
+            Next::EndGame => self.end_game(),
+        }
+    }
+
+    /// Shows everyone the gallery and goes back to the lobby so that a new game can
+    /// be started
+    fn end_game(&mut self) {
+        trace!(
+            "Room {} finished game after {} rounds",
+            self.key,
+            self.rounds.len()
+        );
+        self.round_id += 1;
+        self.draw_history.clear();
+        self.broadcast_event(Event::Gallery(Arc::clone(&self.rounds)));
+        for (_, _, points) in self.occupants.values_mut() {
+            *points = 0;
+        }
+
+        let host = *self
+            .queue
+            .iter()
+            .find(|id| self.occupants.contains_key(id) && !self.bots.contains(id))
+            .expect("room had occupants but none were in the queue");
+        self.state = RoomState::Lobby(LobbyState { host });
+        self.broadcast_event(Event::EnterLobby(host));
+        self.send_settings_data(&self.occupants.get(&host).unwrap().0);
+    }
+
+    pub fn send_gallery(&self, session_id: usize) {
+        if let Some((recipient, _, _)) = self.occupants.get(&session_id) {
+            self.direct_message(recipient, Event::Gallery(Arc::clone(&self.rounds)));
+        } else {
+            warn!(
+                "User {} asked for the gallery of room {} when it wasn't a member",
+                session_id, self.key
+            );
         }
-        error!("Room {} had no possible leader for new round", self.key);
     }
 
     pub fn round_timeout(&mut self, round_id: usize, ctx: &mut Context<GameServer>) {
-        if let RoomState::Round(RoundState { .. }) = self.state {
-            if round_id == self.round_id {
+        if round_id != self.round_id {
+            return;
+        }
+        match self.state {
+            RoomState::Round(RoundState {
+                word, ref leaders, ..
+            }) => {
                 trace!("Room {} has timed out", self.key);
+                self.metrics.round_timed_out(StateName::Round);
+                self.event_log.record(
+                    &self.key,
+                    GameEvent::Timeout {
+                        round: round_id,
+                        state: StateName::Round.label(),
+                        leaders,
+                        word: Some(self.get_word(word)),
+                    },
+                );
                 self.end_round(None, 0, None, ctx);
             }
+            RoomState::Solo => {
+                trace!("Everyone ran out of time in room {}", self.key);
+                let state = self.state_name();
+                self.metrics.round_timed_out(state);
+                self.with_solo_game(ctx, |game, room| {
+                    let (leaders, word) = game.describe_round(room);
+                    room.record(GameEvent::Timeout {
+                        round: round_id,
+                        state: state.label(),
+                        leaders: &leaders,
+                        word: word.as_deref(),
+                    });
+                });
+                self.finish_step(ctx);
+            }
+            _ => {}
+        }
+    }
+
+    /// Moves on once everyone still in the room has finished the current step,
+    /// otherwise lets everyone know who they're waiting on
+    fn progress(&mut self, ctx: &mut Context<GameServer>) {
+        let waiting = self
+            .with_solo_game(ctx, |game, room| game.waiting_on(room))
+            .unwrap_or_default();
+        if waiting.is_empty() {
+            self.finish_step(ctx);
+        } else {
+            self.broadcast_event(Event::Waiting(waiting));
+        }
+    }
+
+    /// Ends the current step whether or not everyone has finished it
+    fn finish_step(&mut self, ctx: &mut Context<GameServer>) {
+        match self.state {
+            RoomState::Solo => {
+                if let Some(next) = self.with_solo_game(ctx, |game, room| game.finish_step(room)) {
+                    self.follow(next, ctx);
+                }
+            }
+            _ => error!("finish_step called with invalid state in room {}", self.key),
+        }
+    }
+
+    /// Handles a player finishing their part of a step where everyone works on
+    /// their own
+    pub fn handle_submit(
+        &mut self,
+        session_id: usize,
+        content: String,
+        ctx: &mut Context<GameServer>,
+    ) {
+        if let RoomState::Solo = self.state {
+            let state = self.state_name();
+            if self.with_solo_game(ctx, |game, room| game.submit(session_id, &content, room))
+                == Some(true)
+            {
+                self.record_submitted(session_id, state);
+                self.progress(ctx);
+            }
+        } else {
+            warn!(
+                "submit sent by {} in invalid state in room {}",
+                session_id, self.key
+            );
+        }
+    }
+
+    fn record_submitted(&self, session_id: usize, state: StateName) {
+        self.event_log.record(
+            &self.key,
+            GameEvent::Submitted {
+                session: session_id,
+                state: state.label(),
+            },
+        );
+    }
+
+    /// Handles a vote in a game where everyone plays at once
+    pub fn handle_vote(&mut self, session_id: usize, data: String, ctx: &mut Context<GameServer>) {
+        if let RoomState::Solo = self.state {
+            let state = self.state_name();
+            if self.with_solo_game(ctx, |game, room| game.vote(session_id, &data, room))
+                == Some(true)
+            {
+                self.record_submitted(session_id, state);
+                self.progress(ctx);
+            }
+        } else {
+            warn!(
+                "vote sent by {} in invalid state in room {}",
+                session_id, self.key
+            );
         }
     }
 
@@ -468,64 +1004,174 @@ impl Room {
         message: String,
         ctx: &mut Context<GameServer>,
     ) {
-        if let RoomState::Round(RoundState { word, leader, .. }) = self.state {
-            if session_id != leader {
-                self.broadcast_event(Event::Message(session_id, message.clone()));
-                let (matches, alternate) = self
-                    .get_wordpack(word.0)
-                    .word_matches(word.1, &message.trim().to_lowercase());
-                if matches {
-                    if let Some((_, _, points)) = self.occupants.get_mut(&session_id) {
-                        *points += 1;
-                        let points = *points;
+        if let RoomState::Round(RoundState {
+            word, ref leaders, ..
+        }) = self.state
+        {
+            let guess = self.mode.evaluate_guess(
+                session_id,
+                leaders,
+                self.get_wordpack(word.0),
+                word.1,
+                &message,
+            );
+            if !matches!(guess, Guess::Blocked) {
+                self.event_log.record(
+                    &self.key,
+                    GameEvent::Guess {
+                        session: session_id,
+                        guess: &self.word_filter.censor(&message),
+                        correct: matches!(guess, Guess::Correct(_)),
+                    },
+                );
+            }
+            match guess {
+                Guess::Correct(alternate) => {
+                    self.broadcast_event(Event::Message(
+                        session_id,
+                        self.word_filter.censor(&message),
+                    ));
+                    let leaders = leaders.clone();
+                    if let Some(points) = self.mode.score(session_id, &leaders, &mut self.occupants)
+                    {
                         self.end_round(Some(session_id), points, alternate, ctx);
                     } else {
                         warn!("winner {} wasn't in room {}", session_id, self.key);
                     }
                 }
-            } else {
-                warn!(
-                    "Leader {} in room {} tried to send guess {}",
-                    leader, self.key, message
-                );
+                Guess::Chat => {
+                    self.broadcast_event(Event::Message(
+                        session_id,
+                        self.word_filter.censor(&message),
+                    ));
+                }
+                Guess::Blocked => {
+                    warn!(
+                        "User {} in room {} tried to send blocked message: {}",
+                        session_id, self.key, message
+                    );
+                    self.send_blocked(session_id, message);
+                }
             }
+        } else if self
+            .with_solo_game(ctx, |game, room| {
+                game.blocks_message(session_id, &message, room)
+            })
+            .unwrap_or(false)
+        {
+            warn!(
+                "User {} in room {} tried to send blocked message: {}",
+                session_id, self.key, message
+            );
+            self.send_blocked(session_id, message);
         } else {
-            self.broadcast_event(Event::Message(session_id, message));
+            self.broadcast_event(Event::Message(
+                session_id,
+                self.word_filter.censor(&message),
+            ));
         }
     }
 
-    pub fn handle_draw(&mut self, session_id: usize, data: String) {
-        if let RoomState::Round(RoundState { leader, .. }) = self.state {
-            if leader != session_id {
-                warn!(
-                    "Uid {} in room {} tried to send draw command when {} was leader",
-                    session_id, self.key, leader
-                );
-                return;
-            }
+    /// Lets the sender know that their message wasn't shown to anyone
+    fn send_blocked(&self, session_id: usize, message: String) {
+        if let Some((recipient, _, _)) = self.occupants.get(&session_id) {
+            self.direct_message(recipient, Event::MessageBlocked(message));
+        }
+    }
 
+    /// Handles the three kinds of draw command: `d` draws a line with the pen, `x`
+    /// rubs out a line with the eraser and `p` flood fills from a point
+    pub fn handle_draw(&mut self, session_id: usize, command: char, data: String) {
+        if let Some((canvas, started)) = self.drawable_canvas(session_id) {
             if let Ok(content) = data
                 .split(',')
                 .map(|x| x.parse::<u32>())
                 .collect::<Result<Vec<_>, _>>()
             {
-                if let [x1, x2, y1, y2, pen_size] = *content {
-                    if x1 <= 500 && x2 <= 500 && y1 <= 500 && y2 <= 500 && pen_size <= 10 {
-                        self.broadcast_event(Event::Draw(x1, x2, y1, y2, pen_size));
-                        self.draw_history.push((x1, x2, y1, y2, pen_size));
+                // The stroke id is optional for lines, commands without one continue the
+                // last stroke if it was drawn with the same tool. Lines without a colour
+                // are drawn in black.
+                let (tool, (x1, y1, x2, y2, pen_size), stroke_id) = match (command, &*content) {
+                    ('d', &[x1, y1, x2, y2, pen_size]) => {
+                        (Tool::Pen(0), (x1, y1, x2, y2, pen_size), None)
+                    }
+                    ('d', &[x1, y1, x2, y2, pen_size, stroke_id]) => {
+                        (Tool::Pen(0), (x1, y1, x2, y2, pen_size), Some(stroke_id))
+                    }
+                    ('d', &[x1, y1, x2, y2, pen_size, stroke_id, colour]) => (
+                        Tool::Pen(colour as usize),
+                        (x1, y1, x2, y2, pen_size),
+                        Some(stroke_id),
+                    ),
+                    ('x', &[x1, y1, x2, y2, pen_size]) => {
+                        (Tool::Eraser, (x1, y1, x2, y2, pen_size), None)
+                    }
+                    ('x', &[x1, y1, x2, y2, pen_size, stroke_id]) => {
+                        (Tool::Eraser, (x1, y1, x2, y2, pen_size), Some(stroke_id))
+                    }
+                    ('p', &[x, y, stroke_id, colour]) => (
+                        Tool::Fill(colour as usize),
+                        (x, y, x, y, 0),
+                        Some(stroke_id),
+                    ),
+                    _ => {
+                        warn!(
+                            "{} in room {} sent a `{}` draw command with the wrong number of parts ({})",
+                            session_id,
+                            self.key,
+                            command,
+                            content.len()
+                        );
+                        return;
+                    }
+                };
+                let colour_valid = match tool {
+                    Tool::Pen(colour) | Tool::Fill(colour) => colour < PALETTE.len(),
+                    Tool::Eraser => true,
+                };
+                // Check the range before converting so that nothing gets truncated
+                let in_range = [x1, y1, x2, y2, pen_size]
+                    .iter()
+                    .all(|x| *x <= u16::MAX as u32);
+                let segment = (x1 as u16, y1 as u16, x2 as u16, y2 as u16, pen_size as u16);
+                if in_range && self.settings.canvas.contains(segment) && colour_valid {
+                    if self.canvas_mut(canvas).segment_count() >= self.settings.max_draw_history {
+                        warn!(
+                            "{} in room {} sent a draw command when the draw history was full",
+                            session_id, self.key
+                        );
+                        return;
+                    }
+                    let stroke_id = match stroke_id {
+                        Some(stroke_id) => stroke_id as usize,
+                        None => self
+                            .canvas_mut(canvas)
+                            .continuing_stroke_id(session_id, tool),
+                    };
+                    let time = started.elapsed().as_millis() as u32;
+                    let pushed = self
+                        .canvas_mut(canvas)
+                        .push_segment(session_id, stroke_id, tool, segment, time);
+                    if let Some(stroke_id) = pushed {
+                        self.metrics.segment_drawn();
+                        let event = Event::Draw(tool, segment, session_id, stroke_id);
+                        self.send_canvas_event(canvas, event);
+                        let simplify_after = self.settings.simplify_draw_history;
+                        let history = self.canvas_mut(canvas);
+                        if history.segment_count() > simplify_after {
+                            history.simplify_finished();
+                        }
                     } else {
                         warn!(
-                            "{} in room {} sent a draw command with parts out of range",
-                            session_id, self.key,
-                        )
+                            "{} in room {} sent a draw command that couldn't continue stroke {}",
+                            session_id, self.key, stroke_id
+                        );
                     }
                 } else {
                     warn!(
-                        "{} in room {} sent a draw command with not enough parts (expected 5 got {})",
-                        session_id,
-                        self.key,
-                        content.len()
-                    );
+                        "{} in room {} sent a draw command with parts out of range",
+                        session_id, self.key,
+                    )
                 }
             } else {
                 warn!(
@@ -535,21 +1181,49 @@ impl Room {
             }
         } else {
             warn!(
-                "draw command sent by {} in invalid state in room {}",
+                "draw command sent by {} in room {} when they couldn't draw",
                 session_id, self.key
             );
         }
     }
 
-    pub fn clear(&mut self, session_id: usize) {
-        if let RoomState::Round(RoundState { leader, .. }) = self.state {
-            if leader != session_id {
-                warn!(
-                    "Uid {} in room {} tried to send clear command when {} was leader",
-                    session_id, self.key, leader
-                );
-                return;
+    /// Finds the canvas the user can draw on, if any, and when drawing on it started
+    fn drawable_canvas(&self, session_id: usize) -> Option<(CanvasId, Instant)> {
+        match &self.state {
+            RoomState::Round(RoundState {
+                leaders, started, ..
+            }) if leaders.contains(&session_id) => Some((CanvasId::Shared, *started)),
+            RoomState::Solo if self.canvases.contains_key(&session_id) => {
+                Some((CanvasId::Personal(session_id), self.canvases_started))
             }
+            _ => None,
+        }
+    }
+
+    fn canvas_mut(&mut self, canvas: CanvasId) -> &mut DrawHistory {
+        match canvas {
+            CanvasId::Shared => &mut self.draw_history,
+            CanvasId::Personal(session_id) => self
+                .canvases
+                .get_mut(&session_id)
+                .expect("personal canvas didn't exist"),
+        }
+    }
+
+    /// Sends an event about a canvas to everyone who can see it
+    fn send_canvas_event(&self, canvas: CanvasId, event: Event) {
+        match canvas {
+            CanvasId::Shared => self.broadcast_event(event),
+            CanvasId::Personal(session_id) => {
+                if let Some((recipient, _, _)) = self.occupants.get(&session_id) {
+                    self.direct_message(recipient, event);
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self, session_id: usize) {
+        if let Some((canvas, _)) = self.drawable_canvas(session_id) {
             if !self.settings.allow_clear {
                 warn!(
                     "Uid {} in room {} tried to send clear command when not enabled",
@@ -557,20 +1231,445 @@ impl Room {
                 );
                 return;
             }
-            self.broadcast_event(Event::ClearCanvas);
-            self.draw_history.clear();
+            self.send_canvas_event(canvas, Event::ClearCanvas);
+            self.canvas_mut(canvas).clear();
         } else {
             warn!(
-                "clear command sent by {} in invalid state in room {}",
+                "clear command sent by {} in room {} when they couldn't draw",
                 session_id, self.key
             );
         }
     }
 
+    pub fn undo(&mut self, session_id: usize) {
+        if let Some((canvas, _)) = self.drawable_canvas(session_id) {
+            if let Some(stroke_id) = self.canvas_mut(canvas).undo(session_id) {
+                self.send_canvas_event(canvas, Event::UndoStroke(session_id, stroke_id));
+            } else {
+                trace!("Nothing to undo in room {}", self.key);
+            }
+        } else {
+            warn!(
+                "undo command sent by {} in room {} when they couldn't draw",
+                session_id, self.key
+            );
+        }
+    }
+
+    pub fn redo(&mut self, session_id: usize) {
+        if let Some((canvas, _)) = self.drawable_canvas(session_id) {
+            if let Some(stroke) = self.canvas_mut(canvas).redo(session_id) {
+                let event = Event::RedoStroke(stroke.clone());
+                self.send_canvas_event(canvas, event);
+            } else {
+                trace!("Nothing to redo in room {}", self.key);
+            }
+        } else {
+            warn!(
+                "redo command sent by {} in room {} when they couldn't draw",
+                session_id, self.key
+            );
+        }
+    }
+
+    /// Gets a finished round, where the first round is 0
+    pub fn get_round(&self, round: usize) -> Option<Arc<RoundRecord>> {
+        self.rounds.get(round).cloned()
+    }
+
+    /// Adds a bot with the next free name if the user is the host
+    pub fn add_bot(
+        &mut self,
+        session_id: usize,
+        bot: usize,
+        recipient: Recipient<Event>,
+        ctx: &mut Context<GameServer>,
+    ) -> bool {
+        match self.state {
+            RoomState::Lobby(LobbyState { host }) if host == session_id => {}
+            _ => {
+                warn!(
+                    "{} tried to add a bot to room {} when they weren't the host",
+                    session_id, self.key
+                );
+                return false;
+            }
+        }
+        if self.bots.len() >= self.config.limits.max_bots {
+            warn!(
+                "{} tried to add a bot to room {} when it had the most bots it can",
+                session_id, self.key
+            );
+            return false;
+        }
+        let username = (1..)
+            .map(|i| format!("Bot {}", i))
+            .find(|name| {
+                self.occupants
+                    .values()
+                    .all(|(_, username, _)| username != name)
+            })
+            .unwrap();
+        self.join(bot, recipient, username, ctx);
+        self.bots.insert(bot);
+        self.broadcast_event(Event::Bots(self.bots.iter().copied().collect()));
+        true
+    }
+
+    /// Removes the bot if the user is the host
+    pub fn remove_bot(
+        &mut self,
+        session_id: usize,
+        bot: usize,
+        ctx: &mut Context<GameServer>,
+    ) -> bool {
+        match self.state {
+            RoomState::Lobby(LobbyState { host }) if host == session_id => {}
+            _ => {
+                warn!(
+                    "{} tried to remove a bot from room {} when they weren't the host",
+                    session_id, self.key
+                );
+                return false;
+            }
+        }
+        if !self.bots.contains(&bot) {
+            warn!(
+                "{} tried to remove {} from room {} when it wasn't a bot",
+                session_id, bot, self.key
+            );
+            return false;
+        }
+        // The host is still in the room so it won't be left empty
+        self.leave(bot, ctx);
+        true
+    }
+
+    pub fn bots(&self) -> &BTreeSet<usize> {
+        &self.bots
+    }
+
+    /// Ends the round when a bot is the only one drawing and doesn't know the word
+    pub fn give_up(&mut self, session_id: usize, ctx: &mut Context<GameServer>) {
+        match self.state {
+            RoomState::Round(RoundState { ref leaders, .. })
+                if self.bots.contains(&session_id) && *leaders == [session_id] =>
+            {
+                trace!("Bot {} gave up drawing in room {}", session_id, self.key);
+                self.end_round(None, 0, None, ctx);
+            }
+            _ => trace!(
+                "Bot {} tried to give up in room {} when it wasn't drawing alone",
+                session_id,
+                self.key
+            ),
+        }
+    }
+
+    /// Every word the current game can pick from, including the custom words
+    pub fn word_list(&self) -> Vec<String> {
+        self.settings
+            .enabled_word_packs
+            .iter()
+            .map(|pack| &self.word_packs[*pack])
+            .chain(self.settings.custom_words.as_ref())
+            .flat_map(|pack| pack.words().cloned())
+            .collect()
+    }
+
+    /// The name of the room's state, used as a label in the metrics
+    pub fn state_name(&self) -> StateName {
+        match self.state {
+            RoomState::Lobby(_) => StateName::Lobby,
+            RoomState::Round(_) => StateName::Round,
+            RoomState::Winner(_) => StateName::Winner,
+            RoomState::Solo => self
+                .mode
+                .solo_game()
+                .expect("room was playing a solo game with a mode that doesn't have one")
+                .state_name(),
+        }
+    }
+
+    /// The room as it is now, for the snapshot written when the server stops
+    pub fn snapshot(&self) -> RoomSnapshot<'_> {
+        let mut players: Vec<_> = self
+            .occupants
+            .iter()
+            .map(|(session_id, (_, username, points))| PlayerSnapshot {
+                session: *session_id,
+                username,
+                points: *points,
+                bot: self.bots.contains(session_id),
+            })
+            .collect();
+        players.sort_unstable_by_key(|player| player.session);
+        RoomSnapshot {
+            key: &self.key,
+            state: self.state_name().label(),
+            mode: GAME_MODES[self.settings.mode].name,
+            rounds_played: self.rounds_played,
+            words: self
+                .rounds
+                .iter()
+                .map(|round| round.word.as_str())
+                .collect(),
+            players,
+        }
+    }
+
+    /// Everyone in the room, sorted so that it's the same every time
     fn get_user_list(&self) -> Vec<(usize, String)> {
-        self.occupants
+        let mut users: Vec<_> = self
+            .occupants
             .iter()
             .map(|(session_id, (_, username, _))| (*session_id, username.clone()))
+            .collect();
+        users.sort_unstable();
+        users
+    }
+}
+
+/// Everything in a room that a game where everyone plays at once can use, see
+/// `SoloGame`
+pub struct RoomHandle<'a> {
+    key: &'a str,
+    occupants: &'a mut HashMap<usize, Occupant>,
+    bots: &'a BTreeSet<usize>,
+    queue: &'a mut VecDeque<usize>,
+    canvases: &'a mut HashMap<usize, DrawHistory>,
+    canvases_started: &'a mut Instant,
+    rounds: &'a mut Arc<Vec<Arc<RoundRecord>>>,
+    rounds_played: &'a mut usize,
+    round_id: &'a mut usize,
+    words: &'a mut WordChooser,
+    word_packs: &'a [WordPack],
+    settings: &'a Settings,
+    word_filter: &'a WordFilter,
+    metrics: &'a Metrics,
+    event_log: &'a EventLog,
+    config: &'a Config,
+    ctx: &'a mut Context<GameServer>,
+}
+
+impl RoomHandle<'_> {
+    pub fn key(&self) -> &str {
+        self.key
+    }
+
+    pub fn config(&self) -> &Config {
+        self.config
+    }
+
+    pub fn word_filter(&self) -> &WordFilter {
+        self.word_filter
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        self.metrics
+    }
+
+    pub fn record(&self, event: GameEvent) {
+        self.event_log.record(self.key, event);
+    }
+
+    /// Identifies the current step, see `start_step`
+    pub fn round_id(&self) -> usize {
+        *self.round_id
+    }
+
+    /// Picks the word for a new round from the enabled word packs
+    pub fn choose_word(&mut self) -> (usize, usize) {
+        self.words.random_word()
+    }
+
+    pub fn word_pack(&self, pack: usize) -> &WordPack {
+        word_pack(self.word_packs, self.settings, pack)
+    }
+
+    pub fn word(&self, word: (usize, usize)) -> &str {
+        self.word_pack(word.0).get_word(word.1)
+    }
+
+    /// Counts a round towards the round limit
+    pub fn round_finished(&mut self) {
+        *self.rounds_played += 1;
+        self.metrics.round_finished();
+    }
+
+    /// Gives the player points, returning their total or `None` if they aren't in
+    /// the room
+    pub fn add_points(&mut self, session_id: usize, points: usize) -> Option<usize> {
+        let (_, _, total) = self.occupants.get_mut(&session_id)?;
+        *total += points;
+        Some(*total)
+    }
+
+    /// Everyone in the room in the order of the queue, which leaves out whoever's
+    /// turn it is, see `take_turn`
+    pub fn players(&self) -> Vec<usize> {
+        self.queue
+            .iter()
+            .copied()
+            .filter(|id| self.occupants.contains_key(id))
             .collect()
     }
+
+    pub fn is_present(&self, session_id: usize) -> bool {
+        self.occupants.contains_key(&session_id)
+    }
+
+    /// Takes the next person in the room off the queue so that it's their turn.
+    /// Bots are skipped since they can't describe words, but keep their place.
+    pub fn take_turn(&mut self) -> Option<usize> {
+        let index = self
+            .queue
+            .iter()
+            .position(|id| self.occupants.contains_key(id) && !self.bots.contains(id))?;
+        self.queue.remove(index)
+    }
+
+    /// Puts the player back at the end of the queue once their turn is over
+    pub fn end_turn(&mut self, session_id: usize) {
+        self.queue.push_back(session_id);
+    }
+
+    pub fn send(&self, session_id: usize, event: Event) {
+        if let Some((recipient, _, _)) = self.occupants.get(&session_id) {
+            send_event(self.metrics, recipient, event);
+        }
+    }
+
+    pub fn broadcast(&self, event: Event) {
+        for (recipient, _, _) in self.occupants.values() {
+            send_event(self.metrics, recipient, event.clone());
+        }
+    }
+
+    /// Starts a new step lasting `limit`, returning when it times out if the round
+    /// timer is on. The timers of earlier steps no longer do anything.
+    pub fn start_step(&mut self, limit: Duration) -> Option<u128> {
+        *self.round_id += 1;
+        start_round_timer(self.settings, self.key, *self.round_id, limit, self.ctx);
+        timeout_after(self.settings, limit)
+    }
+
+    /// Gives each of the players a blank canvas of their own, taking away everyone
+    /// else's
+    pub fn open_canvases(&mut self, players: &[usize]) {
+        *self.canvases = players
+            .iter()
+            .map(|id| (*id, DrawHistory::default()))
+            .collect();
+        *self.canvases_started = Instant::now();
+    }
+
+    /// Gives someone who joined part way through a blank canvas of their own
+    pub fn give_canvas(&mut self, session_id: usize) {
+        self.canvases.insert(session_id, DrawHistory::default());
+    }
+
+    pub fn has_canvas(&self, session_id: usize) -> bool {
+        self.canvases.contains_key(&session_id)
+    }
+
+    /// Everyone who still has a canvas, sorted so that it's the same every time
+    pub fn drawing_players(&self) -> Vec<usize> {
+        let mut players: Vec<_> = self.canvases.keys().copied().collect();
+        players.sort_unstable();
+        players
+    }
+
+    /// Takes the player's canvas and keeps it with the finished rounds so that it
+    /// shows up in the gallery, returning its index. Empty canvases aren't kept.
+    pub fn keep_canvas(&mut self, session_id: usize, word: String) -> Option<usize> {
+        keep_canvas(
+            self.canvases,
+            self.rounds,
+            self.settings.canvas,
+            session_id,
+            word,
+        )
+    }
+
+    /// The strokes of a drawing kept with the finished rounds
+    pub fn drawing(&self, round: usize) -> &[Stroke] {
+        &self.rounds[round].strokes
+    }
+}
+
+/// The word pack with the index, where the one after the last is the custom words
+fn word_pack<'a>(word_packs: &'a [WordPack], settings: &'a Settings, pack: usize) -> &'a WordPack {
+    if pack == word_packs.len() {
+        settings.custom_words.as_ref().unwrap()
+    } else {
+        &word_packs[pack]
+    }
+}
+
+fn send_event(metrics: &Metrics, recipient: &Recipient<Event>, event: Event) {
+    if recipient.do_send(event).is_err() {
+        // TODO: try to fix sending leave message when socket disconnects
+        // so that this is rare, hence upgrading from
+        // trace (common behaviour) to warn (uncommon - indicates bug)
+        trace!("Tried to send message to disconnected socket");
+        metrics.send_dropped();
+    }
+}
+
+/// When something lasting `limit` from now times out, if the round timer is on
+fn timeout_after(settings: &Settings, limit: Duration) -> Option<u128> {
+    use std::time::SystemTime;
+
+    if settings.round_timer {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("went backwards in time");
+        Some((now + limit).as_millis())
+    } else {
+        None
+    }
+}
+
+/// Times out the room's current round after `limit` if the round timer is on
+fn start_round_timer(
+    settings: &Settings,
+    key: &str,
+    round_id: usize,
+    limit: Duration,
+    ctx: &mut Context<GameServer>,
+) {
+    if settings.round_timer {
+        let timer = Timer::RoundTimeout {
+            room: key.to_string(),
+            round: round_id,
+        };
+        GameServer::start_timer(limit, timer, ctx);
+    }
+}
+
+/// Takes the player's canvas and keeps it with the finished rounds, returning its
+/// index. Empty canvases aren't kept.
+fn keep_canvas(
+    canvases: &mut HashMap<usize, DrawHistory>,
+    rounds: &mut Arc<Vec<Arc<RoundRecord>>>,
+    settings: CanvasSettings,
+    session_id: usize,
+    word: String,
+) -> Option<usize> {
+    let mut canvas = canvases.remove(&session_id)?;
+    if canvas.segment_count() == 0 {
+        return None;
+    }
+    canvas.simplify_all();
+    let rounds = Arc::make_mut(rounds);
+    rounds.push(Arc::new(RoundRecord {
+        canvas: settings,
+        word,
+        leader: session_id,
+        winner: None,
+        strokes: canvas.strokes().to_vec(),
+        png: Arc::default(),
+    }));
+    Some(rounds.len() - 1)
 }
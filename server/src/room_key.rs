@@ -0,0 +1,185 @@
+use rand::Rng;
+
+use crate::word_filter::WordFilter;
+
+/// Letters and digits that can't be mistaken for each other, leaving out 0, O, 1, I
+/// and L
+pub const DEFAULT_ALPHABET: &str = "ABCDEFGHJKMNPQRSTUVWXYZ23456789";
+pub const DEFAULT_LENGTH: usize = 5;
+/// The longest a generated or vanity key can be
+pub const MAX_LENGTH: usize = 16;
+/// The shortest vanity key a host can ask for
+pub const MIN_VANITY_LENGTH: usize = 3;
+/// Words that are never allowed in room keys, even without a word filter. Keys are
+/// shown to everyone in the room and shared in links, so this is checked on top of
+/// the configured filter for both vanity and generated keys.
+const BLOCKED_IN_KEYS: &[&str] = &[
+    "fuck", "shit", "cunt", "dick", "twat", "wank", "piss", "slut", "whore", "fag", "nigg", "nazi",
+    "porn", "kkk",
+];
+
+/// Why a host couldn't have the vanity key they asked for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VanityProblem {
+    /// Another room already has the key
+    Taken,
+    /// The key was too short or long, or had something other than letters and
+    /// digits in it
+    Invalid,
+    /// The key had a blocked or filtered word in it
+    Filtered,
+}
+
+impl VanityProblem {
+    pub fn name(self) -> &'static str {
+        match self {
+            VanityProblem::Taken => "taken",
+            VanityProblem::Invalid => "invalid",
+            VanityProblem::Filtered => "filtered",
+        }
+    }
+}
+
+/// How room keys are generated and compared. Keys that aren't case sensitive are
+/// kept in upper case, and anything a client sends is converted before it's looked
+/// up so that players can type them either way.
+#[derive(Debug, Clone)]
+pub struct RoomKeys {
+    alphabet: Vec<char>,
+    length: usize,
+    case_sensitive: bool,
+}
+
+impl Default for RoomKeys {
+    fn default() -> Self {
+        RoomKeys {
+            alphabet: DEFAULT_ALPHABET.chars().collect(),
+            length: DEFAULT_LENGTH,
+            case_sensitive: false,
+        }
+    }
+}
+
+impl RoomKeys {
+    /// Checks that the alphabet only has letters and digits in it, so that keys can
+    /// be sent in messages and URLs as they are, and that it can make keys of the
+    /// given length without running out
+    pub fn new(alphabet: &str, length: usize, case_sensitive: bool) -> Result<RoomKeys, String> {
+        if let Some(c) = alphabet.chars().find(|c| !c.is_ascii_alphanumeric()) {
+            return Err(format!(
+                "room key alphabet can only have letters and digits, not {:?}",
+                c
+            ));
+        }
+        if !(1..=MAX_LENGTH).contains(&length) {
+            return Err(format!(
+                "room keys must be between 1 and {} characters long",
+                MAX_LENGTH
+            ));
+        }
+        let mut chars: Vec<char> = if case_sensitive {
+            alphabet.chars().collect()
+        } else {
+            alphabet.chars().map(|c| c.to_ascii_uppercase()).collect()
+        };
+        chars.sort_unstable();
+        chars.dedup();
+        // Fewer than a few thousand keys would soon all be in use
+        if (chars.len() as f64).powi(length as i32) < 10_000.0 {
+            return Err(format!(
+                "{} characters from an alphabet of {} only make {} different room keys",
+                length,
+                chars.len(),
+                chars.len().pow(length as u32)
+            ));
+        }
+        Ok(RoomKeys {
+            alphabet: chars,
+            length,
+            case_sensitive,
+        })
+    }
+
+    /// A random key from the alphabet, which may be blocked, see `is_blocked`
+    pub fn generate<R: Rng>(&self, rng: &mut R) -> String {
+        (0..self.length)
+            .map(|_| self.alphabet[rng.gen_range(0, self.alphabet.len())])
+            .collect()
+    }
+
+    /// Whether the key has a blocked or filtered word in it. Since keys have no
+    /// spaces, any word inside one counts rather than only whole words.
+    pub fn is_blocked(&self, key: &str, word_filter: &WordFilter) -> bool {
+        let lowercase = key.to_lowercase();
+        BLOCKED_IN_KEYS.iter().any(|word| lowercase.contains(word)) || word_filter.appears_in(key)
+    }
+
+    /// Converts a key sent by a client to the form rooms are stored with
+    pub fn normalise(&self, key: &str) -> String {
+        if self.case_sensitive {
+            key.to_string()
+        } else {
+            key.to_ascii_uppercase()
+        }
+    }
+
+    /// Checks a vanity key a host asked for, returning it normalised. Vanity keys
+    /// can use any letters and digits, not just the alphabet, so that hosts can
+    /// spell words with them.
+    pub fn check_vanity(
+        &self,
+        key: &str,
+        word_filter: &WordFilter,
+    ) -> Result<String, VanityProblem> {
+        if !(MIN_VANITY_LENGTH..=MAX_LENGTH).contains(&key.chars().count())
+            || !key.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(VanityProblem::Invalid);
+        }
+        if self.is_blocked(key, word_filter) {
+            return Err(VanityProblem::Filtered);
+        }
+        Ok(self.normalise(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn generates_the_same_keys_from_the_same_seed() {
+        let keys = RoomKeys::default();
+        let generate = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..10).map(|_| keys.generate(&mut rng)).collect::<Vec<_>>()
+        };
+        assert_eq!(generate(7), generate(7));
+        assert_ne!(generate(7), generate(8));
+    }
+
+    #[test]
+    fn generates_keys_from_the_alphabet() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let keys = RoomKeys::new("abc123", 6, true).unwrap();
+        for _ in 0..100 {
+            let key = keys.generate(&mut rng);
+            assert_eq!(key.chars().count(), 6);
+            assert!(key.chars().all(|c| "abc123".contains(c)), "{}", key);
+        }
+    }
+
+    #[test]
+    fn vanity_keys_with_blocked_words_are_rejected_without_a_filter() {
+        let keys = RoomKeys::default();
+        let filter = WordFilter::default();
+        assert_eq!(keys.check_vanity("party", &filter), Ok("PARTY".to_string()));
+        assert_eq!(
+            keys.check_vanity("BigShit99", &filter),
+            Err(VanityProblem::Filtered)
+        );
+        assert!(keys.is_blocked("XFUCK", &filter));
+        assert!(!keys.is_blocked("HAPPY", &filter));
+    }
+}
@@ -1,32 +1,61 @@
 use actix::prelude::*;
-use rand::{distributions::Alphanumeric, prelude::*, rngs::ThreadRng};
-use std::collections::HashMap;
+use rand::{
+    prelude::*,
+    rngs::{OsRng, StdRng},
+};
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
+use crate::bot::Bot;
+use crate::config::Config;
+use crate::draw_history::{self, Segment, Stroke, Tool};
+use crate::event_log::EventLog;
+use crate::game_mode::{Entry, Prompt, TeamScore};
+use crate::metrics::{Metrics, StateName};
+use crate::recording::{self, Recorder, RecordingProxy};
+use crate::room::RoundRecord;
+use crate::room_key::{RoomKeys, VanityProblem};
+use crate::snapshot;
+use crate::word_filter::WordFilter;
 use crate::word_pack::{load_word_packs, WordPack};
 use crate::Room;
 
 use log::{info, trace, warn};
+use serde::{Deserialize, Serialize};
 
 #[derive(Message, Clone)]
 #[rtype(result = "()")]
 pub enum Event {
     /// Chat message containing username followed by content
     Message(usize, String),
-    /// Draw event containing: (x1, y1, x2, y2, penSize)
-    Draw(u32, u32, u32, u32, u32),
+    /// Draw event containing the tool, the segment drawn, the author and the stroke id
+    Draw(Tool, Segment, usize, usize),
+    /// Everything currently on the canvas, sent to players joining mid-round
+    DrawSnapshot(Vec<Stroke>),
     /// Clears the canvas
     ClearCanvas,
-    /// Start of a new round
-    NewRound(usize, Option<u128>),
-    /// Assign the session a word to draw
-    NewLeader(bool, String, Option<u128>),
+    /// Removes the stroke with the given author and id from the canvas
+    UndoStroke(usize, usize),
+    /// Puts a previously undone stroke back
+    RedoStroke(Stroke),
+    /// Start of a new round, with everyone drawing it
+    NewRound(Vec<usize>, Option<u128>),
+    /// Assign the session a word to draw, along with anyone drawing it with them
+    NewLeader(bool, String, Vec<usize>, Option<u128>),
     /// Join a room. Contains the room code and user list
     EnterRoom(String, Vec<(usize, String)>),
+    /// The canvas size and biggest pen size for the room
+    CanvasSettings(draw_history::CanvasSettings),
     /// Error that indicates that a username already exists within a room
     UsernameExists(String),
     /// Error that indicates that a room key doesn't exist
     NonExistantRoom(String),
+    /// Error that indicates that a room couldn't be created with the vanity key
+    VanityKeyRejected(String, VanityProblem),
+    /// Error that indicates that a chat message wasn't sent since it gave away the
+    /// word, containing the message
+    MessageBlocked(String),
     /// Leave a room
     LeaveRoom,
     /// When a user has won. Contains the username, points, word guessed, and alternate
@@ -37,76 +66,278 @@ pub enum Event {
     UserGone(usize),
     /// Join a lobby. Contains the id of the host
     EnterLobby(usize),
+    /// Every finished round of the game with its drawing
+    Gallery(Arc<Vec<Arc<RoundRecord>>>),
     // Settings suplementary data for client. Wordpack id followed by name and description
     SettingsData(Vec<(usize, String, String)>),
+    /// The points and members of each team, empty if the game mode has no teams
+    Scoreboard(Vec<TeamScore>),
+    /// A step where everyone works on their own has started, with whether it's a
+    /// drawing step, what to draw or describe and the timeout
+    SoloStep(bool, Option<Prompt>, Option<u128>),
+    /// The players that the current step is waiting on
+    Waiting(Vec<usize>),
+    /// Voting has started, with the word, the author and strokes of each drawing, the
+    /// judge if they're the only one who can vote and the timeout
+    Voting(
+        String,
+        Vec<(usize, Vec<Stroke>)>,
+        Option<usize>,
+        Option<u128>,
+    ),
+    /// A reverse round has started, with who is describing the word, the word if
+    /// it's them and the timeout
+    ReverseStep(usize, Option<String>, Option<u128>),
+    /// The word and the author, votes and total points of each drawing
+    VoteResults(String, Vec<(usize, usize, usize)>),
+    /// The author and entry of each step of every chain of a finished telephone game
+    TelephoneChains(Vec<Vec<(usize, Entry)>>),
+    /// The id, name and description of each game mode the host can pick from
+    GameModes(Vec<(usize, &'static str, &'static str)>),
+    /// The session ids of every bot in the room
+    Bots(Vec<usize>),
+    /// The server is restarting at the given time. Games carry on until then, but
+    /// no new rooms can be created.
+    Restarting(u128),
+    /// The server is stopping, so the session should close its connection
+    Closing,
+}
+
+impl Event {
+    /// The same event with every timeout and drawing time set to 0, so that events
+    /// sent at different times can be compared
+    pub fn without_timing(self) -> Event {
+        let untimed = |timeout: Option<u128>| timeout.map(|_| 0);
+        match self {
+            Event::NewRound(leaders, timeout) => Event::NewRound(leaders, untimed(timeout)),
+            Event::NewLeader(clear, word, others, timeout) => {
+                Event::NewLeader(clear, word, others, untimed(timeout))
+            }
+            Event::SoloStep(drawing, prompt, timeout) => {
+                Event::SoloStep(drawing, prompt, untimed(timeout))
+            }
+            Event::Voting(word, drawings, judge, timeout) => {
+                Event::Voting(word, drawings, judge, untimed(timeout))
+            }
+            Event::ReverseStep(clue_giver, word, timeout) => {
+                Event::ReverseStep(clue_giver, word, untimed(timeout))
+            }
+            Event::Restarting(_) => Event::Restarting(0),
+            Event::Gallery(rounds) => {
+                let rounds = rounds
+                    .iter()
+                    .map(|round| {
+                        let mut round = RoundRecord::clone(round);
+                        for stroke in &mut round.strokes {
+                            for time in &mut stroke.times {
+                                *time = 0;
+                            }
+                        }
+                        Arc::new(round)
+                    })
+                    .collect();
+                Event::Gallery(Arc::new(rounds))
+            }
+            event => event,
+        }
+    }
 }
 
 pub struct GameServer {
     rooms: HashMap<String, Room>,
     recipients: HashMap<usize, Recipient<Event>>,
-    rng: ThreadRng,
+    rng: StdRng,
     word_packs: Arc<Vec<WordPack>>,
+    word_filter: Arc<WordFilter>,
+    room_keys: RoomKeys,
+    config: Arc<Config>,
+    /// Drawings of words from finished rounds that someone guessed, for bots to draw
+    /// the same words in later games
+    recordings: HashMap<String, VecDeque<Arc<RoundRecord>>>,
+    metrics: Arc<Metrics>,
+    event_log: Arc<EventLog>,
+    /// Where everything sent into the server is recorded, if anywhere
+    recorder: Option<Arc<Recorder>>,
+    /// Whether a recording is being replayed, see `replaying`
+    replaying: bool,
+    /// The session ids to give out in order when replaying, as they were recorded
+    replay_ids: VecDeque<usize>,
+    /// When the server stops, once it's been told to shut down
+    restarting_at: Option<u128>,
 }
 
 impl GameServer {
-    pub fn new<P: std::fmt::Debug + AsRef<std::path::Path>>(word_pack_dir: P) -> Self {
-        let word_packs = load_word_packs(word_pack_dir).expect("Error loading the word packs");
+    /// Creates the game server from a config that has already been validated
+    pub fn new(
+        config: Config,
+        word_filter: WordFilter,
+        event_log: Arc<EventLog>,
+        seed: u64,
+    ) -> Self {
+        let word_packs =
+            load_word_packs(&config.game.word_pack_dir).expect("Error loading the word packs");
+        let room_keys = config
+            .room_keys
+            .room_keys()
+            .expect("The room key format wasn't validated");
 
         info!(
-            "Game server instance created with {} word packs",
-            word_packs.len()
+            "Game server instance created with {} word packs, {} filtered words and seed {}",
+            word_packs.len(),
+            word_filter.len(),
+            seed
         );
 
         GameServer {
             rooms: HashMap::new(),
             recipients: HashMap::new(),
-            rng: ThreadRng::default(),
+            rng: StdRng::seed_from_u64(seed),
             word_packs: Arc::new(word_packs),
+            word_filter: Arc::new(word_filter),
+            room_keys,
+            config: Arc::new(config),
+            recordings: HashMap::new(),
+            metrics: Arc::new(Metrics::default()),
+            event_log,
+            recorder: None,
+            replaying: false,
+            replay_ids: VecDeque::new(),
+            restarting_at: None,
         }
     }
 
-    fn create_room(&mut self, session_id: usize, username: String) {
-        loop {
-            let key: String = std::iter::repeat(())
-                .map(|()| self.rng.sample(Alphanumeric))
-                .take(5)
-                .collect();
-            if self.rooms.get(&key).is_none() {
-                if let Some(recipient) = self.recipients.get(&session_id) {
-                    let room = Room::new(
-                        key.clone(),
-                        Arc::clone(&self.word_packs),
-                        session_id,
-                        recipient.clone(),
-                        username.clone(),
-                    );
+    /// Records everything sent into the server so that it can be replayed
+    pub fn record_to(mut self, recorder: Arc<Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
 
-                    self.rooms.insert(key.clone(), room);
+    /// Sets the server up to replay a recording, where bots don't do anything
+    /// themselves, timers only go off when the recording says they did and sessions
+    /// are given the ids they were recorded with
+    pub fn replaying(mut self, ids: VecDeque<usize>) -> Self {
+        self.replaying = true;
+        self.replay_ids = ids;
+        self
+    }
 
-                    trace!(
-                        "Room {} was created by user {} ({}), there are now {} rooms",
-                        key,
-                        username,
-                        session_id,
-                        self.rooms.len(),
-                    );
-                } else {
-                    warn!("User creating a room didn't exist");
-                }
-                return;
-            } else {
-                trace!("Tried to create room with key {} but it was taken", key);
+    /// Sets off the timer after the delay, unless replaying
+    pub fn start_timer(delay: Duration, timer: Timer, ctx: &mut Context<GameServer>) {
+        ctx.run_later(delay, move |server, ctx| {
+            if !server.replaying {
+                server.timer_fired(timer, ctx);
             }
+        });
+    }
+
+    fn timer_fired(&mut self, timer: Timer, ctx: &mut Context<GameServer>) {
+        self.record(recording::Entry::Timer {
+            timer: timer.clone(),
+        });
+        match timer {
+            Timer::RoundTimeout { room, round } => self.round_timeout(&room, round, ctx),
+            Timer::NewRound { room } => self.new_round(room, ctx),
+        }
+    }
+
+    fn record(&self, entry: recording::Entry) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(entry);
+        }
+    }
+
+    /// Creates a room with a new key, or with the vanity key the host asked for if
+    /// it's allowed and not already in use
+    fn create_room(&mut self, session_id: usize, username: String, vanity: Option<&str>) {
+        let recipient = if let Some(recipient) = self.recipients.get(&session_id) {
+            recipient.clone()
+        } else {
+            warn!("User creating a room didn't exist");
+            return;
+        };
+        if let Some(restarting_at) = self.restarting_at {
+            trace!(
+                "User {} tried to create a room while the server is restarting",
+                session_id
+            );
+            let _ = recipient.do_send(Event::Restarting(restarting_at));
+            return;
         }
+        let key = match vanity {
+            Some(vanity) => {
+                let key = self
+                    .room_keys
+                    .check_vanity(vanity, &self.word_filter)
+                    .and_then(|key| {
+                        if self.rooms.contains_key(&key) {
+                            Err(VanityProblem::Taken)
+                        } else {
+                            Ok(key)
+                        }
+                    });
+                match key {
+                    Ok(key) => key,
+                    Err(problem) => {
+                        trace!(
+                            "User {} couldn't create room with vanity key {}: {}",
+                            session_id,
+                            vanity,
+                            problem.name()
+                        );
+                        let _ = recipient
+                            .do_send(Event::VanityKeyRejected(vanity.to_string(), problem));
+                        return;
+                    }
+                }
+            }
+            None => loop {
+                let key = self.room_keys.generate(&mut self.rng);
+                if self.rooms.contains_key(&key) {
+                    trace!("Tried to create room with key {} but it was taken", key);
+                } else if self.room_keys.is_blocked(&key, &self.word_filter) {
+                    trace!("Generated room key {} had a blocked word in it", key);
+                } else {
+                    break key;
+                }
+            },
+        };
+        let room = Room::new(
+            key.clone(),
+            Arc::clone(&self.word_packs),
+            Arc::clone(&self.word_filter),
+            Arc::clone(&self.metrics),
+            Arc::clone(&self.event_log),
+            Arc::clone(&self.config),
+            StdRng::seed_from_u64(self.rng.gen()),
+            session_id,
+            recipient,
+            username.clone(),
+        );
+
+        self.rooms.insert(key.clone(), room);
+
+        trace!(
+            "Room {} was created by user {} ({}), there are now {} rooms",
+            key,
+            username,
+            session_id,
+            self.rooms.len(),
+        );
     }
 
-    fn join_room(&mut self, key: &str, username: String, session_id: usize) {
+    fn join_room(
+        &mut self,
+        key: &str,
+        username: String,
+        session_id: usize,
+        ctx: &mut Context<GameServer>,
+    ) {
         let recipient = self
             .recipients
             .get(&session_id)
             .expect("session_id did not exist");
-        if let Some(room) = self.rooms.get_mut(key) {
-            room.join(session_id, recipient.clone(), username);
+        if let Some(room) = self.rooms.get_mut(&self.room_keys.normalise(key)) {
+            room.join(session_id, recipient.clone(), username, ctx);
         } else {
             // Perfectly normal user behaviour (e.g. enter wrong key by accident)
             let _ = recipient.do_send(Event::NonExistantRoom(key.to_string()));
@@ -123,6 +354,11 @@ impl GameServer {
         if let Some(room) = self.rooms.get_mut(key) {
             // If room after the session leaving is now empty, delete it
             if room.leave(session_id, ctx) {
+                // Bots don't stay in a room without any people in it
+                let bots: Vec<_> = room.bots().iter().copied().collect();
+                for bot in bots {
+                    self.disconnect(bot);
+                }
                 self.rooms.remove(key);
                 trace!(
                     "Room {} is empty so removing it, {} room(s) left",
@@ -166,6 +402,28 @@ impl GameServer {
         }
     }
 
+    fn handle_undo(&mut self, key: &str, session_id: usize) {
+        if let Some(room) = self.rooms.get_mut(key) {
+            room.undo(session_id);
+        } else {
+            warn!(
+                "User {} tried to undo in non-existant room {}",
+                session_id, key
+            );
+        }
+    }
+
+    fn handle_redo(&mut self, key: &str, session_id: usize) {
+        if let Some(room) = self.rooms.get_mut(key) {
+            room.redo(session_id);
+        } else {
+            warn!(
+                "User {} tried to redo in non-existant room {}",
+                session_id, key
+            );
+        }
+    }
+
     pub fn round_timeout(&mut self, key: &str, round_id: usize, ctx: &mut Context<GameServer>) {
         if let Some(room) = self.rooms.get_mut(key) {
             room.round_timeout(round_id, ctx);
@@ -174,22 +432,107 @@ impl GameServer {
         }
     }
 
-    #[allow(clippy::map_entry)]
-    fn connect(&mut self, recipient: Recipient<Event>) -> usize {
+    /// Adds a bot to the room, which it joins like any other user
+    fn add_bot(&mut self, key: &str, session_id: usize, ctx: &mut Context<GameServer>) {
+        if !self.rooms.contains_key(key) {
+            warn!(
+                "User {} tried to add a bot to non-existant room {}",
+                session_id, key
+            );
+            return;
+        }
+        let id = self.new_session_id();
+        self.record(recording::Entry::Bot { session: id });
+        let mut bot = Bot::new(id, key.to_string(), ctx.address(), self.rng.gen());
+        if self.replaying {
+            bot = bot.muted();
+        }
+        let recipient = bot.start().recipient();
+        let room = self.rooms.get_mut(key).unwrap();
+        if room.add_bot(session_id, id, recipient.clone(), ctx) {
+            self.recipients.insert(id, recipient);
+            trace!("Bot {} added to room {}", id, key);
+        }
+    }
+
+    fn remove_bot(
+        &mut self,
+        key: &str,
+        session_id: usize,
+        bot: usize,
+        ctx: &mut Context<GameServer>,
+    ) {
+        if let Some(room) = self.rooms.get_mut(key) {
+            if room.remove_bot(session_id, bot, ctx) {
+                self.disconnect(bot);
+            }
+        } else {
+            warn!(
+                "User {} tried to remove a bot from non-existant room {}",
+                session_id, key
+            );
+        }
+    }
+
+    /// Keeps a drawing of a guessed word for bots, replacing the oldest one of the
+    /// word if there are already enough
+    fn record_drawing(&mut self, record: Arc<RoundRecord>) {
+        if !self.recordings.contains_key(&record.word)
+            && self.recordings.len() >= self.config.limits.max_recorded_words
+        {
+            return;
+        }
+        let recordings = self.recordings.entry(record.word.clone()).or_default();
+        if recordings.len() >= self.config.limits.recordings_per_word {
+            recordings.pop_front();
+        }
+        recordings.push_back(record);
+    }
+
+    /// A random unused id, 0 is never used since it means a session hasn't got one yet.
+    /// Ids come from the OS rather than the seeded RNG so that knowing the seed
+    /// doesn't let anyone work them out, or the recording when replaying.
+    fn new_session_id(&mut self) -> usize {
+        if self.replaying {
+            if let Some(id) = self.replay_ids.pop_front() {
+                return id;
+            }
+        }
         loop {
-            let id: usize = self.rng.gen();
+            let id: usize = OsRng.gen();
             if !self.recipients.contains_key(&id) && id != 0 {
-                self.recipients.insert(id, recipient);
-                info!(
-                    "Recipient given id {}, there are now {} user(s) connected",
-                    id,
-                    self.recipients.len()
-                );
                 return id;
             }
         }
     }
 
+    fn connect(&mut self, recipient: Recipient<Event>) -> usize {
+        let id = self.new_session_id();
+        let recipient = match &self.recorder {
+            Some(recorder) => {
+                recorder.record(recording::Entry::Connect { session: id });
+                RecordingProxy {
+                    session_id: id,
+                    recipient,
+                    recorder: Arc::clone(recorder),
+                }
+                .start()
+                .recipient()
+            }
+            None => recipient,
+        };
+        if let Some(restarting_at) = self.restarting_at {
+            let _ = recipient.do_send(Event::Restarting(restarting_at));
+        }
+        self.recipients.insert(id, recipient);
+        info!(
+            "Recipient given id {}, there are now {} user(s) connected",
+            id,
+            self.recipients.len()
+        );
+        id
+    }
+
     fn disconnect(&mut self, id: usize) {
         self.recipients.remove(&id);
         trace!(
@@ -199,6 +542,27 @@ impl GameServer {
         );
     }
 
+    /// Writes the snapshot if there's somewhere to, then tells every session to
+    /// close its connection. Rooms go once everyone has left them.
+    fn close_sessions(&mut self) {
+        if let Some(path) = &self.config.shutdown.snapshot {
+            let mut rooms: Vec<_> = self.rooms.values().map(Room::snapshot).collect();
+            rooms.sort_unstable_by_key(|room| room.key);
+            match snapshot::write(path, rooms) {
+                Ok(()) => info!(
+                    "Wrote a snapshot of {} room(s) to {:?}",
+                    self.rooms.len(),
+                    path
+                ),
+                Err(e) => warn!("Couldn't write the snapshot to {:?}: {}", path, e),
+            }
+        }
+        info!("Closing {} session(s)", self.recipients.len());
+        for recipient in self.recipients.values() {
+            let _ = recipient.do_send(Event::Closing);
+        }
+    }
+
     pub fn new_round(&mut self, room_key: String, ctx: &mut Context<GameServer>) {
         if let Some(room) = self.rooms.get_mut(&room_key) {
             room.new_round(ctx);
@@ -208,6 +572,10 @@ impl GameServer {
 
 impl Actor for GameServer {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(Duration::from_secs(1), |act, _| act.metrics.tick());
+    }
 }
 
 #[derive(Message)]
@@ -231,16 +599,87 @@ pub struct DisconnectMessage {
     pub room: Option<String>,
 }
 
+/// Asks for a finished round in a room, used to export drawings
+#[derive(Message)]
+#[rtype(result = "Option<Arc<RoundRecord>>")]
+pub struct DrawingRequest {
+    pub room: String,
+    pub round: usize,
+}
+
+/// Asks for the server's metrics in the Prometheus text format
+#[derive(Message)]
+#[rtype(result = "String")]
+pub struct MetricsRequest;
+
+/// Asks for the drawings of the word from earlier games, for a bot to draw one of
+#[derive(Message)]
+#[rtype(result = "Vec<Arc<RoundRecord>>")]
+pub struct RecordingRequest {
+    pub word: String,
+}
+
+/// Keeps the drawing from a finished round for bots
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RecordDrawing {
+    pub record: Arc<RoundRecord>,
+}
+
+/// Asks for every word a room's game can pick from, for bots to guess with
+#[derive(Message)]
+#[rtype(result = "Vec<String>")]
+pub struct WordListRequest {
+    pub room: String,
+}
+
+/// Warns everyone that the server is restarting and stops new rooms from being
+/// created, responding with how long until it stops
+#[derive(Message)]
+#[rtype(result = "Duration")]
+pub struct Shutdown;
+
+/// Tells every session to close once the shutdown countdown is over
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CloseSessions;
+
+/// Something a room does after a delay. Timers are recorded when they go off so
+/// that a replay can set them off at the same point instead of waiting for them.
+#[derive(Message, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+#[serde(tag = "timer", rename_all = "snake_case")]
+pub enum Timer {
+    /// The step of the room with the round id has run out of time
+    RoundTimeout { room: String, round: usize },
+    /// The intermission is over and the room starts its next round
+    NewRound { room: String },
+}
+
+/// A bot that doesn't know how to draw its word ends the round
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct GiveUp {
+    pub session_id: usize,
+    pub room: String,
+}
+
 impl Handler<ClientMessage> for GameServer {
     type Result = ();
 
     fn handle(&mut self, msg: ClientMessage, ctx: &mut Context<Self>) {
+        self.record(recording::Entry::Message {
+            session: msg.session_id,
+            room: msg.room.clone(),
+            content: msg.content.clone(),
+        });
         let type_char = if let Some(char) = msg.content.chars().next() {
             char
         } else {
             warn!("User {} sent empty message (no type_char)", msg.session_id,);
             return;
         };
+        self.metrics.message_received(type_char);
         match (msg.room, type_char) {
             (Some(room_key), 'm') => {
                 let chat: String = msg.content.chars().skip(1).collect();
@@ -262,10 +701,10 @@ impl Handler<ClientMessage> for GameServer {
                     );
                 }
             }
-            (Some(room_key), 'd') => {
+            (Some(room_key), 'd') | (Some(room_key), 'x') | (Some(room_key), 'p') => {
                 let data: String = msg.content.chars().skip(1).collect();
                 if let Some(room) = self.rooms.get_mut(&room_key) {
-                    room.handle_draw(msg.session_id, data);
+                    room.handle_draw(msg.session_id, type_char, data);
                 } else {
                     warn!(
                         "User {} was marked as being in non-existant room {} when sending draw command",
@@ -285,12 +724,65 @@ impl Handler<ClientMessage> for GameServer {
             (Some(room_key), 'c') => {
                 self.handle_clear(&room_key, msg.session_id);
             }
+            (Some(room_key), 'a') => {
+                if let Some(room) = self.rooms.get(&room_key) {
+                    room.send_gallery(msg.session_id);
+                } else {
+                    warn!(
+                        "User {} was marked as being in non-existant room {} when asking for the gallery",
+                        msg.session_id, room_key
+                    );
+                }
+            }
+            (Some(room_key), 'u') => {
+                let content: String = msg.content.chars().skip(1).collect();
+                if let Some(room) = self.rooms.get_mut(&room_key) {
+                    room.handle_submit(msg.session_id, content, ctx);
+                } else {
+                    warn!(
+                        "User {} was marked as being in non-existant room {} when submitting",
+                        msg.session_id, room_key
+                    );
+                }
+            }
+            (Some(room_key), 'v') => {
+                let data: String = msg.content.chars().skip(1).collect();
+                if let Some(room) = self.rooms.get_mut(&room_key) {
+                    room.handle_vote(msg.session_id, data, ctx);
+                } else {
+                    warn!(
+                        "User {} was marked as being in non-existant room {} when voting",
+                        msg.session_id, room_key
+                    );
+                }
+            }
+            // b+ adds a bot and b- followed by its id removes one
+            (Some(room_key), 'b') => match msg.content.get(1..2) {
+                Some("+") => self.add_bot(&room_key, msg.session_id, ctx),
+                Some("-") => match msg.content[2..].parse() {
+                    Ok(bot) => self.remove_bot(&room_key, msg.session_id, bot, ctx),
+                    Err(_) => warn!(
+                        "User {} tried to remove a bot without a valid id in room {}",
+                        msg.session_id, room_key
+                    ),
+                },
+                _ => warn!(
+                    "User {} sent invalid bot message in room {}: {}",
+                    msg.session_id, room_key, msg.content
+                ),
+            },
+            (Some(room_key), 'z') => {
+                self.handle_undo(&room_key, msg.session_id);
+            }
+            (Some(room_key), 'y') => {
+                self.handle_redo(&room_key, msg.session_id);
+            }
             (None, 'j') => {
                 let data = msg.content.chars().skip(1).collect::<String>();
                 let components = data.split(',').collect::<Vec<_>>();
                 if let [key, username] = *components {
                     if validate_username(username) {
-                        self.join_room(&key, username.to_string(), msg.session_id);
+                        self.join_room(key, username.to_string(), msg.session_id, ctx);
                     } else {
                         warn!(
                             "{} sent invalid username {} when joining room {}",
@@ -306,9 +798,14 @@ impl Handler<ClientMessage> for GameServer {
                 }
             }
             (None, 'n') => {
-                let username: String = msg.content.chars().skip(1).collect();
+                let data: String = msg.content.chars().skip(1).collect();
+                // Usernames can't have commas, so anything after one is a vanity key
+                let (username, vanity) = match data.find(',') {
+                    Some(i) => (data[..i].to_string(), Some(&data[i + 1..])),
+                    None => (data.clone(), None),
+                };
                 if validate_username(&username) {
-                    self.create_room(msg.session_id, username);
+                    self.create_room(msg.session_id, username, vanity);
                 } else {
                     warn!(
                         "{} sent invalid username {} when creating room",
@@ -333,10 +830,132 @@ impl Handler<ConnectMessage> for GameServer {
         self.connect(msg.recipient)
     }
 }
+impl Handler<DrawingRequest> for GameServer {
+    type Result = Option<Arc<RoundRecord>>;
+
+    fn handle(&mut self, msg: DrawingRequest, _: &mut Context<Self>) -> Self::Result {
+        self.rooms
+            .get(&self.room_keys.normalise(&msg.room))
+            .and_then(|room| room.get_round(msg.round))
+    }
+}
+
+impl Handler<MetricsRequest> for GameServer {
+    type Result = String;
+
+    fn handle(&mut self, _: MetricsRequest, _: &mut Context<Self>) -> String {
+        let rooms: Vec<_> = StateName::ALL
+            .iter()
+            .map(|state| {
+                let count = self
+                    .rooms
+                    .values()
+                    .filter(|room| room.state_name() == *state)
+                    .count();
+                (*state, count)
+            })
+            .collect();
+        self.metrics.render(self.recipients.len(), &rooms)
+    }
+}
+
+impl Handler<RecordingRequest> for GameServer {
+    type Result = MessageResult<RecordingRequest>;
+
+    fn handle(&mut self, msg: RecordingRequest, _: &mut Context<Self>) -> Self::Result {
+        MessageResult(
+            self.recordings
+                .get(&msg.word)
+                .map(|recordings| recordings.iter().cloned().collect())
+                .unwrap_or_default(),
+        )
+    }
+}
+
+impl Handler<RecordDrawing> for GameServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: RecordDrawing, _: &mut Context<Self>) {
+        self.record_drawing(msg.record);
+    }
+}
+
+impl Handler<WordListRequest> for GameServer {
+    type Result = MessageResult<WordListRequest>;
+
+    fn handle(&mut self, msg: WordListRequest, _: &mut Context<Self>) -> Self::Result {
+        MessageResult(
+            self.rooms
+                .get(&msg.room)
+                .map(|room| room.word_list())
+                .unwrap_or_default(),
+        )
+    }
+}
+
+impl Handler<GiveUp> for GameServer {
+    type Result = ();
+
+    fn handle(&mut self, msg: GiveUp, ctx: &mut Context<Self>) {
+        self.record(recording::Entry::GiveUp {
+            session: msg.session_id,
+            room: msg.room.clone(),
+        });
+        if let Some(room) = self.rooms.get_mut(&msg.room) {
+            room.give_up(msg.session_id, ctx);
+        }
+    }
+}
+
+impl Handler<Shutdown> for GameServer {
+    type Result = MessageResult<Shutdown>;
+
+    fn handle(&mut self, _: Shutdown, _: &mut Context<Self>) -> Self::Result {
+        self.record(recording::Entry::Shutdown);
+        let countdown = self.config.shutdown.countdown();
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("went backwards in time");
+        let restarting_at = (now + countdown).as_millis();
+        self.restarting_at = Some(restarting_at);
+        info!(
+            "Restarting in {} second(s), warning {} user(s) in {} room(s)",
+            countdown.as_secs(),
+            self.recipients.len(),
+            self.rooms.len()
+        );
+        for recipient in self.recipients.values() {
+            let _ = recipient.do_send(Event::Restarting(restarting_at));
+        }
+        MessageResult(countdown)
+    }
+}
+
+impl Handler<Timer> for GameServer {
+    type Result = ();
+
+    fn handle(&mut self, timer: Timer, ctx: &mut Context<Self>) {
+        self.timer_fired(timer, ctx);
+    }
+}
+
+impl Handler<CloseSessions> for GameServer {
+    type Result = ();
+
+    fn handle(&mut self, _: CloseSessions, _: &mut Context<Self>) {
+        self.record(recording::Entry::CloseSessions);
+        self.close_sessions();
+    }
+}
+
 impl Handler<DisconnectMessage> for GameServer {
     type Result = ();
 
     fn handle(&mut self, msg: DisconnectMessage, ctx: &mut Context<Self>) {
+        self.record(recording::Entry::Disconnect {
+            session: msg.session_id,
+            room: msg.room.clone(),
+        });
         self.disconnect(msg.session_id);
         if let Some(room) = msg.room {
             self.leave_room(&room, msg.session_id, ctx);
@@ -345,5 +964,5 @@ impl Handler<DisconnectMessage> for GameServer {
 }
 
 fn validate_username(username: &str) -> bool {
-    !username.contains(',') && username.len() < 15
+    !username.contains(',') && !username.chars().any(char::is_control) && username.len() < 15
 }
@@ -1,6 +1,8 @@
 use actix::prelude::*;
 use actix_web_actors::ws;
 
+use crate::draw_history::{Segment, Stroke, Tool};
+use crate::game_mode::{Entry, Prompt};
 use crate::server::*;
 
 use log::{error, warn};
@@ -52,58 +54,242 @@ impl Handler<Event> for Session {
             error!("server wants to send event but id was 0 (uninitialised), this is an internal error");
             return;
         }
-        let message = match event {
-            Event::Message(username, msg) => format!("m{},{}", username, msg),
-            Event::EnterRoom(room_name, users) => {
-                self.room = Some(room_name.clone());
-                let mut output = String::with_capacity(1024);
-                output.push_str(&format!("e{}", room_name));
-                for (session_id, username) in users {
-                    output.push_str(&format!(",{},{}", session_id, username));
-                }
-                output
-            }
-            // fk = failure key
-            Event::NonExistantRoom(key) => format!("fk{}", key),
-            // fu = failure username
-            Event::UsernameExists(username) => format!("fu{}", username),
-            Event::LeaveRoom => {
-                self.room = None;
-                "q".to_string()
-            }
-            Event::Draw(x1, y1, x2, y2, pen_size) => {
-                format!("d{},{},{},{},{}", x1, y1, x2, y2, pen_size)
-            }
-            Event::ClearCanvas => "b".to_string(),
-            Event::NewRound(username, timeout) => format!("r{},{}", username, timeout.unwrap_or(0)),
-            Event::NewLeader(canvas_clearing, word, timeout) => format!(
+        match &event {
+            Event::EnterRoom(room_name, _) => self.room = Some(room_name.clone()),
+            Event::LeaveRoom => self.room = None,
+            Event::Closing => {
+                // 1012 tells the client the server is restarting, so it can
+                // reconnect
+                ctx.close(Some(ws::CloseReason {
+                    code: ws::CloseCode::Restart,
+                    description: Some("The server is restarting".to_string()),
+                }));
+                ctx.stop();
+                return;
+            }
+            _ => {}
+        }
+        ctx.text(format_event(event));
+    }
+}
+
+/// Formats an event as the text sent to the client. The first character is the
+/// type of event.
+pub fn format_event(event: Event) -> String {
+    match event {
+        Event::Message(username, msg) => format!("m{},{}", username, msg),
+        Event::EnterRoom(room_name, users) => {
+            let mut output = String::with_capacity(1024);
+            output.push_str(&format!("e{}", room_name));
+            for (session_id, username) in users {
+                output.push_str(&format!(",{},{}", session_id, username));
+            }
+            output
+        }
+        Event::CanvasSettings(canvas) => format!(
+            "k{},{},{}",
+            canvas.width, canvas.height, canvas.max_pen_size
+        ),
+        // fk = failure key
+        Event::NonExistantRoom(key) => format!("fk{}", key),
+        // fc = failure code, when a host can't have the vanity key they asked for
+        Event::VanityKeyRejected(key, problem) => format!("fc{},{}", problem.name(), key),
+        // fu = failure username
+        Event::UsernameExists(username) => format!("fu{}", username),
+        // fb = failure blocked, when a chat message gave away the word
+        Event::MessageBlocked(message) => format!("fb{}", message),
+        Event::LeaveRoom => "q".to_string(),
+        Event::Draw(tool, segment, author, stroke_id) => {
+            format_draw(tool, segment, author, stroke_id)
+        }
+        // One draw command per line, in the order they were drawn
+        Event::DrawSnapshot(strokes) => {
+            let mut output = "h".to_string();
+            push_strokes(&mut output, strokes);
+            output
+        }
+        Event::ClearCanvas => "b".to_string(),
+        Event::UndoStroke(author, stroke_id) => format!("z{},{}", author, stroke_id),
+        // The first line is the author and stroke id, then each segment of the
+        // stroke is sent on its own line in the same format as a normal draw event
+        Event::RedoStroke(stroke) => {
+            let mut output = format!("y{},{}", stroke.author, stroke.id);
+            for segment in stroke.segments {
+                output.push('\n');
+                output.push_str(&format_draw(stroke.tool, segment, stroke.author, stroke.id));
+            }
+            output
+        }
+        // r = new round, with the timeout followed by everyone drawing
+        Event::NewRound(leaders, timeout) => {
+            let mut output = format!("r{}", timeout.unwrap_or(0));
+            for leader in leaders {
+                output.push_str(&format!(",{}", leader));
+            }
+            output
+        }
+        // l = new leader, followed by anyone else drawing after the timeout
+        Event::NewLeader(canvas_clearing, word, others, timeout) => {
+            let mut output = format!(
                 "l{}{},{}",
                 if canvas_clearing { 'T' } else { 'F' },
                 word,
                 timeout.unwrap_or(0)
+            );
+            for other in others {
+                output.push_str(&format!(",{}", other));
+            }
+            output
+        }
+        Event::Winner(winner, points, word, alternate) => match winner {
+            Some(id) => format!(
+                "wT{},{},{},{}",
+                id,
+                points,
+                word,
+                alternate.unwrap_or_default()
             ),
-            Event::Winner(winner, points, word, alternate) => match winner {
-                Some(id) => format!(
-                    "wT{},{},{},{}",
-                    id,
-                    points,
-                    word,
-                    alternate.unwrap_or_default()
-                ),
-                None => format!("wF{}", word),
-            },
-            Event::UserJoin(session_id, username) => format!("j{},{}", session_id, username),
-            Event::UserGone(session_id) => format!("g{}", session_id),
-            Event::EnterLobby(host_id) => format!("o{}", host_id),
-            Event::SettingsData(wordpacks) => {
-                let mut string = "s".to_string();
-                for (id, name, description) in wordpacks {
-                    string.push_str(&format!("\n{},{},{}", id, name, description));
+            None => format!("wF{}", word),
+        },
+        Event::UserJoin(session_id, username) => format!("j{},{}", session_id, username),
+        Event::UserGone(session_id) => format!("g{}", session_id),
+        Event::EnterLobby(host_id) => format!("o{}", host_id),
+        // Each round starts with a line containing the leader, winner (empty if
+        // nobody won), canvas width and height and word, followed by its draw commands
+        // one per line. Each command is prefixed with when it was drawn so that
+        // the round can be replayed.
+        Event::Gallery(rounds) => {
+            let mut output = "a".to_string();
+            for round in rounds.iter() {
+                output.push_str(&format!(
+                    "\n#{},{},{},{},{}",
+                    round.leader,
+                    round.winner.map(|id| id.to_string()).unwrap_or_default(),
+                    round.canvas.width,
+                    round.canvas.height,
+                    round.word
+                ));
+                for stroke in &round.strokes {
+                    for (segment, time) in stroke.segments.iter().zip(&stroke.times) {
+                        output.push_str(&format!(
+                            "\n{}:{}",
+                            time,
+                            format_draw(stroke.tool, *segment, stroke.author, stroke.id)
+                        ));
+                    }
                 }
-                string
             }
-        };
-        ctx.text(message);
+            output
+        }
+        Event::SettingsData(wordpacks) => {
+            let mut string = "s".to_string();
+            for (id, name, description) in wordpacks {
+                string.push_str(&format!("\n{},{},{}", id, name, description));
+            }
+            string
+        }
+        // t = teams, one line per team with its points followed by its members
+        Event::Scoreboard(teams) => {
+            let mut string = "t".to_string();
+            for team in teams {
+                string.push_str(&format!("\n{}", team.points));
+                for member in team.members {
+                    string.push_str(&format!(",{}", member));
+                }
+            }
+            string
+        }
+        // iW = writing step, iD = drawing step, when everyone works on their own.
+        // If there is something to describe or draw it follows on the next line,
+        // either T and the text or D followed by a line for each draw command.
+        Event::SoloStep(drawing, prompt, timeout) => {
+            let mut output = format!(
+                "i{}{}",
+                if drawing { 'D' } else { 'W' },
+                timeout.unwrap_or(0)
+            );
+            match prompt {
+                Some(Prompt::Text(text)) => output.push_str(&format!("\nT{}", text)),
+                Some(Prompt::Drawing(strokes)) => {
+                    output.push_str("\nD");
+                    push_strokes(&mut output, strokes);
+                }
+                None => {}
+            }
+            output
+        }
+        // iC = reverse round with who is describing the word, followed by T and
+        // the word on the next line for the one describing it
+        Event::ReverseStep(clue_giver, word, timeout) => {
+            let mut output = format!("iC{},{}", timeout.unwrap_or(0), clue_giver);
+            if let Some(word) = word {
+                output.push_str(&format!("\nT{}", word));
+            }
+            output
+        }
+        // iP = players the step is waiting on
+        Event::Waiting(waiting) => {
+            let waiting: Vec<_> = waiting.iter().map(|id| id.to_string()).collect();
+            format!("iP{}", waiting.join(","))
+        }
+        // nV = voting, nJ = judging with the judge after the timeout. Each drawing
+        // starts with a # line with its author followed by a line for each draw
+        // command.
+        Event::Voting(word, drawings, judge, timeout) => {
+            let mut output = match judge {
+                Some(judge) => format!("nJ{},{},{}", timeout.unwrap_or(0), judge, word),
+                None => format!("nV{},{}", timeout.unwrap_or(0), word),
+            };
+            for (author, strokes) in drawings {
+                output.push_str(&format!("\n#{}", author));
+                push_strokes(&mut output, strokes);
+            }
+            output
+        }
+        // nR = vote results, a line per drawing with its author, votes and the
+        // author's total points
+        Event::VoteResults(word, results) => {
+            let mut output = format!("nR{}", word);
+            for (author, votes, points) in results {
+                output.push_str(&format!("\n{},{},{}", author, votes, points));
+            }
+            output
+        }
+        // u = telephone chains, each chain starts with a # line followed by a line
+        // per entry: T with the author and text, D with the author and the index
+        // of the drawing in the gallery, or S with the author if they skipped
+        Event::TelephoneChains(chains) => {
+            let mut output = "u".to_string();
+            for chain in chains {
+                output.push_str("\n#");
+                for (author, entry) in chain {
+                    output.push_str(&match entry {
+                        Entry::Text(text) => format!("\nT{},{}", author, text),
+                        Entry::Drawing(round) => format!("\nD{},{}", author, round),
+                        Entry::Skipped => format!("\nS{}", author),
+                    });
+                }
+            }
+            output
+        }
+        // v = variants of the game
+        Event::GameModes(modes) => {
+            let mut string = "v".to_string();
+            for (id, name, description) in modes {
+                string.push_str(&format!("\n{},{},{}", id, name, description));
+            }
+            string
+        }
+        // B = bots, followed by the id of each one
+        Event::Bots(bots) => {
+            let bots: Vec<_> = bots.iter().map(|id| id.to_string()).collect();
+            format!("B{}", bots.join(","))
+        }
+        // R = the server is restarting, followed by when it stops
+        Event::Restarting(stops_at) => format!("R{}", stops_at),
+        // RX = the server is closing the connection. Sessions send a close frame
+        // instead, so this is only seen in recordings.
+        Event::Closing => "RX".to_string(),
     }
 }
 
@@ -147,3 +333,35 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for Session {
         }
     }
 }
+
+/// Adds every segment of the strokes on a new line each, in the order they were
+/// drawn
+fn push_strokes(output: &mut String, strokes: Vec<Stroke>) {
+    for stroke in strokes {
+        for segment in stroke.segments {
+            output.push('\n');
+            output.push_str(&format_draw(stroke.tool, segment, stroke.author, stroke.id));
+        }
+    }
+}
+
+/// Formats a draw command the same way the client sends it, with the author added
+/// on the end since stroke ids are only unique among an author's strokes
+fn format_draw(
+    tool: Tool,
+    (x1, y1, x2, y2, pen_size): Segment,
+    author: usize,
+    stroke_id: usize,
+) -> String {
+    match tool {
+        Tool::Pen(colour) => format!(
+            "d{},{},{},{},{},{},{},{}",
+            x1, y1, x2, y2, pen_size, stroke_id, colour, author
+        ),
+        Tool::Eraser => format!(
+            "x{},{},{},{},{},{},{}",
+            x1, y1, x2, y2, pen_size, stroke_id, author
+        ),
+        Tool::Fill(colour) => format!("p{},{},{},{},{}", x1, y1, stroke_id, colour, author),
+    }
+}
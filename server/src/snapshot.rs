@@ -0,0 +1,46 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+use serde::Serialize;
+
+/// Every room as it was when the server stopped, so that what was going on can be
+/// looked at after a restart
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    /// Milliseconds since the unix epoch
+    time: u128,
+    rooms: Vec<RoomSnapshot<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct RoomSnapshot<'a> {
+    pub key: &'a str,
+    pub state: &'static str,
+    pub mode: &'static str,
+    pub rounds_played: usize,
+    /// The word of each finished round of the current or most recent game
+    pub words: Vec<&'a str>,
+    pub players: Vec<PlayerSnapshot<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct PlayerSnapshot<'a> {
+    pub session: usize,
+    pub username: &'a str,
+    pub points: usize,
+    pub bot: bool,
+}
+
+/// Writes the rooms to the file as JSON, replacing it if it exists
+pub fn write(path: &Path, rooms: Vec<RoomSnapshot>) -> std::io::Result<()> {
+    let time = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("went backwards in time")
+        .as_millis();
+    let mut file = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut file, &Snapshot { time, rooms })?;
+    file.write_all(b"\n")?;
+    file.flush()
+}
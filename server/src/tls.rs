@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::time::{Duration, SystemTime};
+
+use log::{info, warn};
+use rustls::internal::pemfile;
+use rustls::sign::{self, CertifiedKey};
+use rustls::{ClientHello, NoClientAuth, ResolvesServerCert, ServerConfig};
+
+/// How often the certificate and key are checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Gives every connection the current certificate, which is reloaded whenever the
+/// certificate or key file changes so that renewed certificates are used without
+/// restarting the server
+pub struct CertResolver {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    /// The certificate and when its files were last modified
+    current: RwLock<(CertifiedKey, Option<SystemTime>)>,
+}
+
+impl CertResolver {
+    pub fn new(cert_path: PathBuf, key_path: PathBuf) -> io::Result<CertResolver> {
+        let modified = last_modified(&cert_path, &key_path);
+        let cert = load(&cert_path, &key_path)?;
+        Ok(CertResolver {
+            cert_path,
+            key_path,
+            current: RwLock::new((cert, modified)),
+        })
+    }
+
+    fn current(&self) -> RwLockReadGuard<'_, (CertifiedKey, Option<SystemTime>)> {
+        self.current.read().expect("certificate lock was poisoned")
+    }
+
+    /// Reloads the certificate if either file has changed. If the new files can't be
+    /// loaded, e.g. because only one of them has been replaced so far, the old
+    /// certificate is kept and they're tried again next time.
+    fn reload_if_changed(&self) {
+        let modified = last_modified(&self.cert_path, &self.key_path);
+        if modified == self.current().1 {
+            return;
+        }
+        match load(&self.cert_path, &self.key_path) {
+            Ok(cert) => {
+                info!("Reloaded the TLS certificate from {:?}", self.cert_path);
+                *self.current.write().expect("certificate lock was poisoned") = (cert, modified);
+            }
+            Err(e) => warn!("Couldn't reload the TLS certificate: {}", e),
+        }
+    }
+
+    /// Checks the files for changes every `RELOAD_INTERVAL` for as long as the
+    /// server runs
+    pub fn watch(self: Arc<Self>) {
+        actix_rt::spawn(async move {
+            loop {
+                actix_rt::time::delay_for(RELOAD_INTERVAL).await;
+                self.reload_if_changed();
+            }
+        });
+    }
+
+    /// The config for TLS connections, which only offers HTTP/1.1 since websockets
+    /// can't be used over HTTP/2
+    pub fn server_config(self: Arc<Self>) -> Arc<ServerConfig> {
+        let mut config = ServerConfig::new(NoClientAuth::new());
+        config.cert_resolver = self;
+        config.set_protocols(&[b"http/1.1".to_vec()]);
+        Arc::new(config)
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _: ClientHello) -> Option<CertifiedKey> {
+        Some(self.current().0.clone())
+    }
+}
+
+/// When either file was last modified, so that a change to either can be noticed
+fn last_modified(cert_path: &Path, key_path: &Path) -> Option<SystemTime> {
+    let modified = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+    modified(cert_path).max(modified(key_path))
+}
+
+/// Loads a PEM certificate chain and a PKCS #8 or RSA private key
+fn load(cert_path: &Path, key_path: &Path) -> io::Result<CertifiedKey> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let open = |path: &Path| {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| io::Error::new(e.kind(), format!("couldn't open {:?}: {}", path, e)))
+    };
+
+    let certs = pemfile::certs(&mut open(cert_path)?)
+        .ok()
+        .filter(|certs| !certs.is_empty())
+        .ok_or_else(|| invalid(format!("no certificates found in {:?}", cert_path)))?;
+    let key = pemfile::pkcs8_private_keys(&mut open(key_path)?)
+        .ok()
+        .and_then(|keys| keys.into_iter().next())
+        .or_else(|| {
+            pemfile::rsa_private_keys(&mut open(key_path).ok()?)
+                .ok()
+                .and_then(|keys| keys.into_iter().next())
+        })
+        .ok_or_else(|| invalid(format!("no private key found in {:?}", key_path)))?;
+    let key = sign::any_supported_type(&key)
+        .map_err(|_| invalid(format!("unsupported private key in {:?}", key_path)))?;
+
+    let cert = CertifiedKey::new(certs, Arc::new(key));
+    cert.cross_check_end_entity_cert(None)
+        .map_err(|e| invalid(format!("invalid certificate in {:?}: {}", cert_path, e)))?;
+    Ok(cert)
+}
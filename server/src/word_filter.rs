@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Result};
+use std::path::Path;
+
+use log::trace;
+
+/// A list of words that get censored out of chat messages
+#[derive(Debug, Default)]
+pub struct WordFilter {
+    words: HashSet<String>,
+}
+
+impl WordFilter {
+    pub fn new<P: std::fmt::Debug + AsRef<Path>>(path: &P) -> Result<WordFilter> {
+        let mut file = File::open(path)?;
+
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        let words: HashSet<String> = contents
+            .lines()
+            .map(|line| line.trim().to_lowercase())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+
+        trace!("loaded word filter {:?} with {} words", path, words.len());
+
+        Ok(WordFilter { words })
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Replaces every filtered word in the message with asterisks. Words are
+    /// matched case-insensitively on alphanumeric boundaries so that e.g.
+    /// filtering "ass" doesn't censor "class".
+    pub fn censor(&self, message: &str) -> String {
+        if self.words.is_empty() {
+            return message.to_string();
+        }
+
+        let mut output = String::with_capacity(message.len());
+        let mut word = String::new();
+        for c in message.chars() {
+            if c.is_alphanumeric() {
+                word.push(c);
+            } else {
+                self.push_censored(&mut output, &word);
+                word.clear();
+                output.push(c);
+            }
+        }
+        self.push_censored(&mut output, &word);
+        output
+    }
+
+    /// Whether any filtered word appears anywhere in the text, even inside
+    /// another word
+    pub fn appears_in(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        self.words.iter().any(|word| text.contains(word.as_str()))
+    }
+
+    fn push_censored(&self, output: &mut String, word: &str) {
+        if self.words.contains(&word.to_lowercase()) {
+            output.push_str(&"*".repeat(word.chars().count()));
+        } else {
+            output.push_str(word);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(words: &[&str]) -> WordFilter {
+        WordFilter {
+            words: words.iter().map(|word| word.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn censors_whole_words_in_any_case() {
+        let filter = filter(&["heck", "darn"]);
+        assert_eq!(
+            filter.censor("Heck, that's DARN good"),
+            "****, that's **** good"
+        );
+        assert_eq!(filter.censor("heck!heck?"), "****!****?");
+        assert_eq!(filter.censor("(darn)"), "(****)");
+    }
+
+    #[test]
+    fn doesnt_censor_words_that_only_contain_a_filtered_word() {
+        let filter = filter(&["ass"]);
+        assert_eq!(filter.censor("first class passes"), "first class passes");
+        assert_eq!(filter.censor("ass-essment"), "***-essment");
+    }
+
+    #[test]
+    fn filtered_words_appear_inside_other_words() {
+        let filter = filter(&["heck"]);
+        assert!(filter.appears_in("CHECKMATE"));
+        assert!(!filter.appears_in("he ck"));
+        assert!(!WordFilter::default().appears_in("heck"));
+    }
+}
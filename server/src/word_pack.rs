@@ -4,8 +4,9 @@ use std::iter::FromIterator;
 use std::path::Path;
 
 use log::{trace, warn};
+use rand::{prelude::*, rngs::StdRng};
 
-use std::collections::HashSet;
+use std::collections::{HashSet, VecDeque};
 
 #[derive(Debug)]
 pub struct WordPack {
@@ -81,6 +82,11 @@ impl WordPack {
         &self.description
     }
 
+    /// Every main word in the pack, without the alternates
+    pub fn words(&self) -> impl Iterator<Item = &String> {
+        self.list.iter().map(|(word, _)| word)
+    }
+
     pub fn get_word(&self, index: usize) -> &String {
         &self.list[index].0
     }
@@ -102,6 +108,58 @@ impl WordPack {
             (false, None)
         }
     }
+
+    /// Whether the word or any of its alternates appear anywhere in the message.
+    /// They have to start a word, so "car" appears in "cars" and "caring" but not
+    /// "scary". Letters spelled out one at a time, like "c a r", count as a word.
+    pub fn word_appears_in(&self, index: usize, message: &str) -> bool {
+        let message = normalise_words(message);
+        let spelled_out = join_letters(&message);
+        let (word, alternates) = &self.list[index];
+        std::iter::once(word)
+            .chain(alternates.iter())
+            .map(|word| normalise_words(word))
+            .any(|word| {
+                let word = word.trim_end();
+                !word.is_empty() && (message.contains(word) || spelled_out.contains(word))
+            })
+    }
+}
+
+/// Lowercases the text and replaces all runs of non-alphanumeric characters with
+/// a single space, padding either end so that words can be searched for with their
+/// boundaries.
+fn normalise_words(text: &str) -> String {
+    let mut output = String::with_capacity(text.len() + 2);
+    output.push(' ');
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            output.push(c);
+        } else if !output.ends_with(' ') {
+            output.push(' ');
+        }
+    }
+    if !output.ends_with(' ') {
+        output.push(' ');
+    }
+    output
+}
+
+/// Joins up runs of single letters in text from `normalise_words`, so that a word
+/// spelled out one letter at a time can be searched for
+fn join_letters(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut after_letter = false;
+    for token in text.split(' ').filter(|token| !token.is_empty()) {
+        let letter = token.chars().count() == 1;
+        if !(letter && after_letter) {
+            output.push(' ');
+        }
+        output.push_str(token);
+        after_letter = letter;
+    }
+    output.push(' ');
+    output
 }
 
 impl FromIterator<(String, Vec<String>)> for WordPack {
@@ -114,6 +172,63 @@ impl FromIterator<(String, Vec<String>)> for WordPack {
     }
 }
 
+/// Picks random words from the word packs enabled for a game, avoiding words that
+/// came up recently
+pub struct WordChooser {
+    rng: StdRng,
+    /// The id and number of words of each enabled word pack
+    pack_sizes: Vec<(usize, usize)>,
+    num_words: usize,
+    excluded_words: VecDeque<usize>,
+    max_excluded_words: usize,
+}
+
+impl WordChooser {
+    pub fn new(pack_sizes: Vec<(usize, usize)>, seed: u64) -> WordChooser {
+        let num_words = pack_sizes.iter().map(|(_, len)| len).sum::<usize>();
+        WordChooser {
+            rng: StdRng::seed_from_u64(seed),
+            pack_sizes,
+            num_words,
+            excluded_words: VecDeque::new(),
+            max_excluded_words: std::cmp::min(100, num_words / 10),
+        }
+    }
+
+    pub fn num_words(&self) -> usize {
+        self.num_words
+    }
+
+    /// Returns the word pack id and the index of a word within it
+    pub fn random_word(&mut self) -> (usize, usize) {
+        loop {
+            let word_index = self.rng.gen_range(0, self.num_words);
+            if !self.excluded_words.contains(&word_index) {
+                if self.excluded_words.len() >= self.max_excluded_words {
+                    self.excluded_words.pop_front();
+                }
+                if self.excluded_words.len() < self.max_excluded_words {
+                    self.excluded_words.push_back(word_index);
+                }
+                let mut acc = 0;
+                for (pack, len) in &self.pack_sizes {
+                    if len + acc > word_index {
+                        return (*pack, word_index - acc);
+                    }
+                    acc += len;
+                }
+                unreachable!("word_index was out of bounds of all wordpacks");
+            }
+        }
+    }
+}
+
+impl Default for WordChooser {
+    fn default() -> WordChooser {
+        WordChooser::new(Vec::new(), 0)
+    }
+}
+
 pub fn load_word_packs<P: std::fmt::Debug + AsRef<std::path::Path>>(
     word_pack_path: P,
 ) -> std::io::Result<Vec<WordPack>> {
@@ -137,3 +252,81 @@ pub fn load_word_packs<P: std::fmt::Debug + AsRef<std::path::Path>>(
     }
     Ok(word_packs)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word_pack(list: &[(&str, &[&str])]) -> WordPack {
+        WordPack {
+            name: "Test".to_string(),
+            description: String::new(),
+            list: list
+                .iter()
+                .map(|(word, alternates)| {
+                    let alternates = alternates.iter().map(|x| x.to_string()).collect();
+                    (word.to_string(), alternates)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn words_appear_in_messages_in_any_case_and_punctuation() {
+        let pack = word_pack(&[("ice cream", &["icecream"]), ("car", &[])]);
+        assert!(pack.word_appears_in(0, "I love ICE CREAM!"));
+        assert!(pack.word_appears_in(0, "ice-cream"));
+        assert!(pack.word_appears_in(0, "ice   cream?"));
+        assert!(pack.word_appears_in(0, "icecream"));
+        assert!(pack.word_appears_in(1, "a car."));
+        assert!(pack.word_appears_in(1, "Car"));
+    }
+
+    #[test]
+    fn words_only_appear_on_word_boundaries() {
+        let pack = word_pack(&[("ice cream", &[]), ("car", &[])]);
+        assert!(!pack.word_appears_in(1, "that's scary"));
+        assert!(!pack.word_appears_in(1, "a scar"));
+        assert!(!pack.word_appears_in(0, "nice creamy"));
+    }
+
+    #[test]
+    fn words_appear_at_the_start_of_longer_words() {
+        let pack = word_pack(&[("ice cream", &[]), ("car", &[])]);
+        assert!(pack.word_appears_in(1, "cars"));
+        assert!(pack.word_appears_in(1, "caring"));
+        assert!(pack.word_appears_in(1, "He CARED"));
+        assert!(pack.word_appears_in(0, "ice creams"));
+    }
+
+    #[test]
+    fn words_appear_when_spelled_out() {
+        let pack = word_pack(&[("ice cream", &[]), ("car", &[])]);
+        assert!(pack.word_appears_in(1, "c a r"));
+        assert!(pack.word_appears_in(1, "the answer is C-A-R"));
+        assert!(pack.word_appears_in(1, "c a r s"));
+        assert!(pack.word_appears_in(0, "i c e cream"));
+        assert!(!pack.word_appears_in(1, "a c a t"));
+    }
+
+    #[test]
+    fn word_chooser_picks_the_same_words_from_the_same_seed() {
+        let pick = |seed| {
+            let mut words = WordChooser::new(vec![(0, 10), (2, 5)], seed);
+            (0..20).map(|_| words.random_word()).collect::<Vec<_>>()
+        };
+        assert_eq!(pick(1), pick(1));
+        assert_ne!(pick(1), pick(2));
+    }
+
+    #[test]
+    fn word_chooser_avoids_recent_words() {
+        // A tenth of the words are kept from coming up again
+        let mut words = WordChooser::new(vec![(0, 100)], 3);
+        let picked: Vec<_> = (0..1000).map(|_| words.random_word()).collect();
+        for recent in picked.windows(11) {
+            let word = recent[10];
+            assert!(!recent[..10].contains(&word), "{:?} came up again", word);
+        }
+    }
+}